@@ -1,14 +1,16 @@
 // Minimal agent-first CLI — canonical pattern for tools built on agent-first-data.
 //
 // Demonstrates the correct use of: try_parse, cli_parse_output, cli_parse_log_filters,
-// cli_output, and build_cli_error.
+// cli_output, build_cli_error, and LogFormat::from_str + afd_tracing::init.
 //
 // Run:  cargo run --example agent_cli -- echo --output json
 //       API_KEY_SECRET=sk-example cargo run --example agent_cli -- echo --output yaml --log startup,request
+//       cargo run --example agent_cli -- echo --log-format compact
 // Test: cargo test --examples
 
 #![allow(clippy::print_stdout)]
 
+use agent_first_data::afd_tracing::LogFormat;
 use agent_first_data::{build_cli_error, cli_output, cli_parse_log_filters, cli_parse_output};
 use clap::Parser;
 
@@ -22,6 +24,10 @@ struct Cli {
     #[arg(long, default_value = "json")]
     output: String,
 
+    /// Log format: json (default), plain, yaml, compact, silent
+    #[arg(long, default_value = "json")]
+    log_format: String,
+
     /// Log categories (comma-separated): startup, request, ...
     #[arg(long, value_delimiter = ',')]
     log: Vec<String>,
@@ -49,10 +55,18 @@ fn main() {
         std::process::exit(2);
     });
 
-    // Step 3: parse --log with shared helper (trim + lowercase + dedup)
+    // Step 3: parse --log-format with FromStr — no bespoke cli_parse_* helper
+    // needed once the target type implements it
+    let log_format: LogFormat = cli.log_format.parse().unwrap_or_else(|e: String| {
+        println!("{}", agent_first_data::output_json(&build_cli_error(&e)));
+        std::process::exit(2);
+    });
+    agent_first_data::afd_tracing::init(tracing_subscriber::EnvFilter::new("info"), log_format);
+
+    // Step 4: parse --log with shared helper (trim + lowercase + dedup)
     let log = cli_parse_log_filters(&cli.log);
 
-    // Step 4: optionally emit startup diagnostic event
+    // Step 5: optionally emit startup diagnostic event
     if startup_log_enabled(&log) {
         let startup = agent_first_data::build_json(
             "log",
@@ -73,7 +87,7 @@ fn main() {
         println!("{}", cli_output(&startup, format));
     }
 
-    // Step 5: do work, emit result
+    // Step 6: do work, emit result
     let result = agent_first_data::build_json_ok(
         serde_json::json!({
             "action": cli.action,
@@ -103,6 +117,16 @@ mod tests {
         assert!(cli_parse_output("xml").is_err());
     }
 
+    #[test]
+    fn parse_log_format_all_variants() {
+        assert!(matches!("json".parse(), Ok(LogFormat::Json)));
+        assert!(matches!("PLAIN".parse(), Ok(LogFormat::Plain)));
+        assert!(matches!("yaml".parse(), Ok(LogFormat::Yaml)));
+        assert!(matches!("compact".parse(), Ok(LogFormat::Compact)));
+        assert!(matches!("silent".parse(), Ok(LogFormat::Silent)));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
     #[test]
     fn parse_log_normalizes() {
         let f = cli_parse_log_filters(&["Startup", " REQUEST ", "startup"]);
@@ -10,19 +10,41 @@
 //!
 //! # Usage
 //! ```ignore
-//! use agent_first_data::afd_tracing;
+//! use agent_first_data::afd_tracing::{self, LogFormat};
 //! use tracing_subscriber::EnvFilter;
 //!
-//! afd_tracing::init_json(EnvFilter::new("info"));
-//! afd_tracing::init_plain(EnvFilter::new("info"));
-//! afd_tracing::init_yaml(EnvFilter::new("debug"));
+//! afd_tracing::init(EnvFilter::new("info"), LogFormat::Json);
+//! afd_tracing::init(EnvFilter::new("info"), LogFormat::Plain);
+//! afd_tracing::init(EnvFilter::new("debug"), LogFormat::Yaml);
+//! ```
+//!
+//! # Writing elsewhere than stdout
+//! `init` writes to stdout. To send AFD log lines to a file, a byte buffer
+//! (for tests), or a rolling appender,
+//! build the layer directly and plug in any [`MakeWriter`]:
+//! ```ignore
+//! use agent_first_data::afd_tracing::{AfdLayer, LogFormat};
+//! use tracing_subscriber::layer::SubscriberExt;
+//! use tracing_subscriber::util::SubscriberInitExt;
+//!
+//! // `tracing-appender` gives you a non-blocking, daily-rolled `MakeWriter`:
+//! let file_appender = tracing_appender::rolling::daily("/var/log/myapp", "afd.jsonl");
+//! let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
+//!
+//! let layer = AfdLayer::builder(LogFormat::Json)
+//!     .with_writer(non_blocking)
+//!     .build();
+//!
+//! tracing_subscriber::registry().with(layer).init();
 //! ```
 
-use std::io::{self, Write};
+use std::io::Write;
 
 use tracing::field::{Field, Visit};
 use tracing::span;
 use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::layer::Context;
 use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::Layer;
@@ -33,57 +55,274 @@ pub enum LogFormat {
     Json,
     Plain,
     Yaml,
+    /// Single-line JSON with only `code`, `message`, and explicitly-set
+    /// span/event fields — `target` and `timestamp_epoch_ms` are dropped.
+    /// For low-overhead, high-volume logging where every byte costs.
+    Compact,
+    /// The layer is installed (spans and macros still run normally) but
+    /// every event is dropped before formatting or writing. Useful for
+    /// benchmarking tracing overhead without I/O in the mix.
+    Silent,
 }
 
-/// A tracing Layer that outputs AFD-compliant log lines to stdout.
+impl core::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(LogFormat::Json),
+            "plain" => Ok(LogFormat::Plain),
+            "yaml" => Ok(LogFormat::Yaml),
+            "compact" => Ok(LogFormat::Compact),
+            "silent" | "none" => Ok(LogFormat::Silent),
+            other => Err(format!(
+                "invalid log format '{other}' (expected json, plain, yaml, compact, or silent)"
+            )),
+        }
+    }
+}
+
+/// A tracing Layer that outputs AFD-compliant log lines to a configurable
+/// writer (stdout by default).
 pub struct AfdLayer {
     format: LogFormat,
+    with_current_span: bool,
+    with_span_list: bool,
+    with_span_timing: bool,
+    parse_json_fields: bool,
+    writer: BoxMakeWriter,
 }
 
-/// Initialize tracing with AFD JSON output (single-line JSONL).
-pub fn init_json(filter: tracing_subscriber::EnvFilter) {
-    init_with_format(filter, LogFormat::Json);
+impl AfdLayer {
+    /// Start building a layer for `format`. Span-name context (`span`/
+    /// `spans`), span timing, and JSON field parsing are off by default,
+    /// preserving the existing single-line shape — opt in via
+    /// [`AfdLayerBuilder::with_current_span`],
+    /// [`AfdLayerBuilder::with_span_list`],
+    /// [`AfdLayerBuilder::with_span_timing`], and
+    /// [`AfdLayerBuilder::parse_json_fields`]. Writes to stdout by default —
+    /// redirect via [`AfdLayerBuilder::with_writer`].
+    pub fn builder(format: LogFormat) -> AfdLayerBuilder {
+        AfdLayerBuilder {
+            format,
+            with_current_span: false,
+            with_span_list: false,
+            with_span_timing: false,
+            parse_json_fields: false,
+            writer: BoxMakeWriter::new(std::io::stdout),
+        }
+    }
 }
 
-/// Initialize tracing with AFD plain/logfmt output (keys stripped, values formatted).
-pub fn init_plain(filter: tracing_subscriber::EnvFilter) {
-    init_with_format(filter, LogFormat::Plain);
+/// Builder for [`AfdLayer`], mirroring `tracing-subscriber`'s JSON
+/// formatter's `with_current_span`/`with_span_list`/`with_writer` options so
+/// events can be correlated back to the spans they were emitted in and
+/// routed to any destination a [`MakeWriter`] can reach.
+///
+/// ```ignore
+/// use agent_first_data::afd_tracing::{AfdLayer, LogFormat};
+///
+/// let layer = AfdLayer::builder(LogFormat::Json)
+///     .with_current_span(true)
+///     .with_span_list(true)
+///     .build();
+/// ```
+pub struct AfdLayerBuilder {
+    format: LogFormat,
+    with_current_span: bool,
+    with_span_list: bool,
+    with_span_timing: bool,
+    parse_json_fields: bool,
+    writer: BoxMakeWriter,
 }
 
-/// Initialize tracing with AFD YAML output (multi-line, keys stripped, values formatted).
-pub fn init_yaml(filter: tracing_subscriber::EnvFilter) {
-    init_with_format(filter, LogFormat::Yaml);
+impl AfdLayerBuilder {
+    /// Emit a `span` field naming the event's leaf (innermost) span.
+    pub fn with_current_span(mut self, enabled: bool) -> Self {
+        self.with_current_span = enabled;
+        self
+    }
+
+    /// Emit a `spans` array naming every span the event is nested in, in
+    /// root-to-leaf order.
+    pub fn with_span_list(mut self, enabled: bool) -> Self {
+        self.with_span_list = enabled;
+        self
+    }
+
+    /// On every span close, emit a `code="span_close"` line naming the span
+    /// with `duration_ms` (total wall-clock), `busy_ms` (time the span was
+    /// entered), and `idle_ms` (`duration_ms` minus `busy_ms`) — mirroring
+    /// what `tracing-subscriber`'s `fmt` layer offers via `with_timer`.
+    pub fn with_span_timing(mut self, enabled: bool) -> Self {
+        self.with_span_timing = enabled;
+        self
+    }
+
+    /// Parse string field values that look like JSON (first non-whitespace
+    /// byte is `{` or `[`) into a real `serde_json::Value` instead of a
+    /// quoted string, so structured data round-trips instead of being
+    /// escaped. Off by default: turning it on can change a field's JSON type
+    /// out from under a caller that genuinely meant to log a string
+    /// starting with `{`/`[`. A field named with a `json.` prefix (e.g.
+    /// `json.payload`) is always parsed and the prefix stripped, regardless
+    /// of this setting — see [`json_value`].
+    pub fn with_parse_json_fields(mut self, enabled: bool) -> Self {
+        self.parse_json_fields = enabled;
+        self
+    }
+
+    /// Direct output to `make_writer` instead of stdout — a file, an
+    /// in-memory buffer for tests, or a `tracing-appender` rolling/
+    /// non-blocking writer. A fresh handle is acquired per event via
+    /// [`MakeWriter::make_writer`], matching how `tracing-subscriber`'s own
+    /// formatters use the trait.
+    pub fn with_writer<M>(mut self, make_writer: M) -> Self
+    where
+        M: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+    {
+        self.writer = BoxMakeWriter::new(make_writer);
+        self
+    }
+
+    /// Finish building the layer.
+    pub fn build(self) -> AfdLayer {
+        AfdLayer {
+            format: self.format,
+            with_current_span: self.with_current_span,
+            with_span_list: self.with_span_list,
+            with_span_timing: self.with_span_timing,
+            parse_json_fields: self.parse_json_fields,
+            writer: self.writer,
+        }
+    }
 }
 
-fn init_with_format(filter: tracing_subscriber::EnvFilter, format: LogFormat) {
+/// Initialize tracing with AFD output in the given [`LogFormat`], writing to
+/// stdout. For a non-stdout writer or span-name context, build an
+/// [`AfdLayer`] via [`AfdLayer::builder`] and install it manually instead.
+pub fn init(filter: tracing_subscriber::EnvFilter, format: LogFormat) {
     use tracing_subscriber::layer::SubscriberExt;
     use tracing_subscriber::util::SubscriberInitExt;
 
     tracing_subscriber::registry()
         .with(filter)
-        .with(AfdLayer { format })
+        .with(AfdLayer::builder(format).build())
         .init();
 }
 
 /// Stored in span extensions to carry structured fields.
 struct SpanFields(Vec<(String, serde_json::Value)>);
 
+/// Stored in span extensions when [`AfdLayerBuilder::with_span_timing`] is
+/// on: `created` marks when the span was first created, `busy` accumulates
+/// time spent entered (summed across re-entries), and `last_enter` is the
+/// `Instant` of the current entry, if the span is presently entered.
+struct SpanTiming {
+    created: std::time::Instant,
+    busy: std::time::Duration,
+    last_enter: Option<std::time::Instant>,
+}
+
 impl<S> Layer<S> for AfdLayer
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
 {
     fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
-        let mut visitor = JsonVisitor::new();
+        let mut visitor = JsonVisitor::new(self.parse_json_fields);
         attrs.record(&mut visitor);
 
         if let Some(span) = ctx.span(id) {
-            span.extensions_mut().insert(SpanFields(visitor.fields));
+            let mut extensions = span.extensions_mut();
+            extensions.insert(SpanFields(visitor.fields));
+            if self.with_span_timing {
+                extensions.insert(SpanTiming {
+                    created: std::time::Instant::now(),
+                    busy: std::time::Duration::ZERO,
+                    last_enter: None,
+                });
+            }
         }
     }
 
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if !self.with_span_timing {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                timing.last_enter = Some(std::time::Instant::now());
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if !self.with_span_timing {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                if let Some(last_enter) = timing.last_enter.take() {
+                    timing.busy += last_enter.elapsed();
+                }
+            }
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        if !self.with_span_timing || matches!(self.format, LogFormat::Silent) {
+            return;
+        }
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+
+        let mut span_fields = Vec::new();
+        for ancestor in span.scope().from_root() {
+            let extensions = ancestor.extensions();
+            if let Some(fields) = extensions.get::<SpanFields>() {
+                span_fields.extend(fields.0.iter().cloned());
+            }
+        }
+
+        let (total_ms, busy_ms, idle_ms) = {
+            let extensions = span.extensions();
+            let Some(timing) = extensions.get::<SpanTiming>() else {
+                return;
+            };
+            let total = timing.created.elapsed();
+            let busy = timing.busy.min(total);
+            let idle = total - busy;
+            (total.as_millis() as u64, busy.as_millis() as u64, idle.as_millis() as u64)
+        };
+
+        let event_fields = vec![
+            ("code".to_string(), serde_json::Value::String("span_close".to_string())),
+            ("duration_ms".to_string(), serde_json::Value::Number(total_ms.into())),
+            ("busy_ms".to_string(), serde_json::Value::Number(busy_ms.into())),
+            ("idle_ms".to_string(), serde_json::Value::Number(idle_ms.into())),
+        ];
+
+        let line = render_event(
+            self.format,
+            *span.metadata().level(),
+            span.metadata().target(),
+            chrono::Utc::now().timestamp_millis(),
+            Some(span.name().to_string()),
+            None,
+            span_fields,
+            None,
+            event_fields,
+        );
+
+        let mut out = self.writer.make_writer();
+        let _ = out.write_all(line.as_bytes());
+        let _ = out.write_all(b"\n");
+    }
+
     fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
         if let Some(span) = ctx.span(id) {
-            let mut visitor = JsonVisitor::new();
+            let mut visitor = JsonVisitor::new(self.parse_json_fields);
             values.record(&mut visitor);
 
             let mut extensions = span.extensions_mut();
@@ -96,116 +335,205 @@ where
     }
 
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if matches!(self.format, LogFormat::Silent) {
+            return;
+        }
+
         let meta = event.metadata();
 
-        // Collect fields from the event
-        let mut visitor = JsonVisitor::new();
+        let mut visitor = JsonVisitor::new(self.parse_json_fields);
         event.record(&mut visitor);
 
-        // Build output object with AFD field names
-        let mut map = serde_json::Map::with_capacity(4 + visitor.fields.len());
-
-        // Default code from level; can be overridden by explicit code = "..." in the macro
-        let default_code = match *meta.level() {
-            Level::TRACE => "trace",
-            Level::DEBUG => "debug",
-            Level::INFO => "info",
-            Level::WARN => "warn",
-            Level::ERROR => "error",
-        };
-
-        map.insert(
-            "timestamp_epoch_ms".into(),
-            serde_json::Value::Number(chrono::Utc::now().timestamp_millis().into()),
-        );
-
-        // "message" field from the tracing macro's format string
-        if let Some(msg) = visitor.message.take() {
-            map.insert("message".into(), serde_json::Value::String(msg));
-        }
-
-        map.insert(
-            "target".into(),
-            serde_json::Value::String(meta.target().to_string()),
-        );
-
-        // Flatten span fields from root to leaf (child overrides parent on collision)
+        // Flatten span fields from root to leaf (child overrides parent on collision),
+        // and collect span names root-to-leaf for `span`/`spans` if enabled.
+        let mut span_fields = Vec::new();
+        let mut span_names = Vec::new();
         if let Some(scope) = ctx.event_scope(event) {
             for span in scope.from_root() {
+                if self.with_current_span || self.with_span_list {
+                    span_names.push(span.name().to_string());
+                }
                 let extensions = span.extensions();
                 if let Some(fields) = extensions.get::<SpanFields>() {
-                    for (k, v) in &fields.0 {
-                        map.insert(k.clone(), v.clone());
-                    }
+                    span_fields.extend(fields.0.iter().cloned());
                 }
             }
         }
 
-        // Append all event-level structured fields (override span fields on collision)
-        let mut has_code = false;
-        for (k, v) in visitor.fields {
-            if k == "code" {
-                has_code = true;
-            }
-            map.insert(k, v);
-        }
-        if !has_code {
-            map.insert(
-                "code".into(),
-                serde_json::Value::String(default_code.to_string()),
-            );
+        let current_span = self.with_current_span.then(|| span_names.last().cloned()).flatten();
+        let span_list = self.with_span_list.then_some(span_names);
+
+        let line = render_event(
+            self.format,
+            *meta.level(),
+            meta.target(),
+            chrono::Utc::now().timestamp_millis(),
+            current_span,
+            span_list,
+            span_fields,
+            visitor.message,
+            visitor.fields,
+        );
+
+        let mut out = self.writer.make_writer();
+        let _ = out.write_all(line.as_bytes());
+        let _ = out.write_all(b"\n");
+    }
+}
+
+/// Fold span fields, event fields, and metadata into the AFD envelope and
+/// render it in `format`. Split out from [`AfdLayer::on_event`] so the
+/// envelope-building and redaction logic can be exercised without a live
+/// `tracing` subscriber.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn render_event(
+    format: LogFormat,
+    level: Level,
+    target: &str,
+    now_ms: i64,
+    current_span: Option<String>,
+    span_list: Option<Vec<String>>,
+    span_fields: Vec<(String, serde_json::Value)>,
+    message: Option<String>,
+    event_fields: Vec<(String, serde_json::Value)>,
+) -> String {
+    // Default code from level; can be overridden by explicit code = "..." in the macro
+    let default_code = match level {
+        Level::TRACE => "trace",
+        Level::DEBUG => "debug",
+        Level::INFO => "info",
+        Level::WARN => "warn",
+        Level::ERROR => "error",
+    };
+
+    let mut map = serde_json::Map::with_capacity(4 + span_fields.len() + event_fields.len());
+
+    map.insert(
+        "timestamp_epoch_ms".into(),
+        serde_json::Value::Number(now_ms.into()),
+    );
+
+    // "message" field from the tracing macro's format string
+    if let Some(msg) = message {
+        map.insert("message".into(), serde_json::Value::String(msg));
+    }
+
+    map.insert(
+        "target".into(),
+        serde_json::Value::String(target.to_string()),
+    );
+
+    if let Some(name) = current_span {
+        map.insert("span".into(), serde_json::Value::String(name));
+    }
+    if let Some(names) = span_list {
+        map.insert(
+            "spans".into(),
+            serde_json::Value::Array(names.into_iter().map(serde_json::Value::String).collect()),
+        );
+    }
+
+    for (k, v) in span_fields {
+        map.insert(k, v);
+    }
+
+    // Append all event-level structured fields (override span fields on collision)
+    let mut has_code = false;
+    for (k, v) in event_fields {
+        if k == "code" {
+            has_code = true;
         }
+        map.insert(k, v);
+    }
+    if !has_code {
+        map.insert(
+            "code".into(),
+            serde_json::Value::String(default_code.to_string()),
+        );
+    }
 
-        let value = serde_json::Value::Object(map);
+    // Compact drops metadata that isn't an explicitly-set span/event field,
+    // keeping only `code`, `message`, and the fields callers actually set.
+    if matches!(format, LogFormat::Compact) {
+        map.remove("target");
+        map.remove("timestamp_epoch_ms");
+    }
 
-        // Format using the library's own output functions
-        let line = match self.format {
-            LogFormat::Json => crate::output_json(&value),
-            LogFormat::Plain => crate::output_plain(&value),
-            LogFormat::Yaml => crate::output_yaml(&value),
-        };
+    let value = serde_json::Value::Object(map);
 
-        let mut out = io::stdout().lock();
-        let _ = out.write_all(line.as_bytes());
-        let _ = out.write_all(b"\n");
+    // Format using the library's own output functions
+    match format {
+        LogFormat::Json | LogFormat::Compact => crate::output_json(&value),
+        LogFormat::Plain => crate::output_plain(&value),
+        LogFormat::Yaml => crate::output_yaml(&value),
+        LogFormat::Silent => String::new(),
     }
 }
 
+/// Render a `serde_json::Value` for attaching to a tracing field under the
+/// `json.<name>` convention, e.g. `tracing::info!(json.payload = %json_value(&v), ...)`
+/// — [`JsonVisitor`] always strips the `json.` prefix and reparses such
+/// fields back into a real nested value, regardless of
+/// [`AfdLayerBuilder::with_parse_json_fields`].
+pub fn json_value(value: &serde_json::Value) -> String {
+    value.to_string()
+}
+
+/// `true` if `s`'s first non-whitespace byte is `{` or `[` — a cheap
+/// pre-check before attempting a full JSON parse on every string field.
+fn looks_like_json(s: &str) -> bool {
+    matches!(s.trim_start().as_bytes().first(), Some(b'{') | Some(b'['))
+}
+
 /// Visitor that collects tracing event fields into a JSON map.
 struct JsonVisitor {
     message: Option<String>,
     fields: Vec<(String, serde_json::Value)>,
+    parse_json_fields: bool,
 }
 
 impl JsonVisitor {
-    fn new() -> Self {
+    fn new(parse_json_fields: bool) -> Self {
         Self {
             message: None,
             fields: Vec::new(),
+            parse_json_fields,
         }
     }
+
+    /// Shared landing spot for `record_debug` and `record_str`: handles the
+    /// `message` field, the `json.<name>` prefix convention (always parsed),
+    /// and opt-in whole-field JSON parsing, falling back to a plain string
+    /// whenever parsing isn't requested or doesn't succeed.
+    fn record_scalar_or_json(&mut self, field: &Field, raw: String) {
+        if field.name() == "message" {
+            self.message = Some(raw);
+            return;
+        }
+
+        if let Some(name) = field.name().strip_prefix("json.") {
+            let value = serde_json::from_str(&raw)
+                .unwrap_or_else(|_| serde_json::Value::String(raw.clone()));
+            self.fields.push((name.to_string(), value));
+            return;
+        }
+
+        let value = if self.parse_json_fields && looks_like_json(&raw) {
+            serde_json::from_str(&raw).unwrap_or(serde_json::Value::String(raw))
+        } else {
+            serde_json::Value::String(raw)
+        };
+        self.fields.push((field.name().to_string(), value));
+    }
 }
 
 impl Visit for JsonVisitor {
     fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
-        let val = format!("{:?}", value);
-        if field.name() == "message" {
-            self.message = Some(val);
-        } else {
-            self.fields
-                .push((field.name().to_string(), serde_json::Value::String(val)));
-        }
+        self.record_scalar_or_json(field, format!("{:?}", value));
     }
 
     fn record_str(&mut self, field: &Field, value: &str) {
-        if field.name() == "message" {
-            self.message = Some(value.to_string());
-        } else {
-            self.fields.push((
-                field.name().to_string(),
-                serde_json::Value::String(value.to_string()),
-            ));
-        }
+        self.record_scalar_or_json(field, value.to_string());
     }
 
     fn record_i64(&mut self, field: &Field, value: i64) {
@@ -238,4 +566,35 @@ impl Visit for JsonVisitor {
         self.fields
             .push((field.name().to_string(), serde_json::Value::Bool(value)));
     }
+
+    /// Preserve the `source()` chain instead of flattening to one `{:?}`
+    /// string: `{"message": <Display of value>, "causes": [<Display of each
+    /// source>, ...]}`. Depth-capped so a cyclic `source()` chain can't loop
+    /// forever.
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        const MAX_DEPTH: usize = 32;
+
+        let message = value.to_string();
+        if field.name() == "message" {
+            self.message = Some(message);
+            return;
+        }
+
+        let mut causes = Vec::new();
+        let mut source = value.source();
+        while let Some(err) = source {
+            if causes.len() >= MAX_DEPTH {
+                break;
+            }
+            causes.push(serde_json::Value::String(err.to_string()));
+            source = err.source();
+        }
+
+        let mut obj = serde_json::Map::with_capacity(2);
+        obj.insert("message".into(), serde_json::Value::String(message));
+        obj.insert("causes".into(), serde_json::Value::Array(causes));
+
+        self.fields
+            .push((field.name().to_string(), serde_json::Value::Object(obj)));
+    }
 }
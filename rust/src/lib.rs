@@ -1,15 +1,69 @@
 //! Agent-First Data (AFD) output formatting and protocol templates.
 //!
-//! Implements the AFD output convention. JSON is the canonical lossless format.
-//! YAML preserves structure with quoted strings. Plain applies suffix-driven
-//! formatting for human readability.
+//! Implements the AFD output convention. JSON is the canonical lossless format
+//! (original keys, no suffix transformation, secrets redacted). YAML and plain
+//! (logfmt) both strip the semantic suffix from each key and render a
+//! human-readable value in its place.
 //!
 //! ```text
 //! --output json|yaml|plain
 //! ```
+//!
+//! The formatting/redaction core builds under `no_std` (with this crate's
+//! `alloc`-backed default toolchain) by disabling the default `std` feature.
+//! [`rules::RuleSet::load`] (YAML parsing), [`stream`] (needs `io::Write`),
+//! and [`afd_tracing`] (needs a `tracing` subscriber) are std-only and
+//! compiled out without it; everything else — [`output_json`],
+//! [`output_yaml`], [`output_plain`], [`output_query`],
+//! [`internal_redact_secrets`], and the `rules::RuleSet` data type itself —
+//! is unaffected.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
 use serde_json::Value;
 
+/// A borrowed-or-owned display key. Suffix stripping (`strip_and_format`)
+/// almost always just returns a prefix of the original key, so it hands
+/// back a borrow of that slice instead of allocating a new `String` per
+/// field; only a user rule's redaction marker or a formatted value needs
+/// ownership.
+pub type CowStr<'a> = Cow<'a, str>;
+
+#[cfg(feature = "std")]
+pub mod afd_tracing;
+pub mod registry;
+pub mod rules;
+#[cfg(feature = "std")]
+pub mod stream;
+
+use registry::SuffixRegistry;
+use rules::RuleSet;
+
+#[cfg(all(test, feature = "std"))]
+#[path = "tests.rs"]
+mod tests;
+
+#[cfg(all(test, feature = "std", feature = "proptest"))]
+#[path = "proptests.rs"]
+mod proptests;
+
 /// Output format for CLI and API responses.
 #[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
 pub enum OutputFormat {
@@ -17,79 +71,250 @@ pub enum OutputFormat {
     Json,
     Yaml,
     Plain,
+    Query,
 }
 
-impl OutputFormat {
-    /// Format a JSON value as a single compact line (JSONL-compatible).
-    pub fn format(&self, value: &Value) -> String {
-        match self {
-            Self::Json => serde_json::to_string(value).unwrap_or_default(),
-            Self::Yaml => to_yaml(value),
-            Self::Plain => to_plain(value),
-        }
+/// Presentation mode for `_epoch_ms` / `_epoch_s` / `_epoch_ns` / `_rfc3339` fields.
+///
+/// `Absolute` (the default) renders RFC 3339. `Relative` renders a humanized
+/// delta ("3m ago", "in 45s") against the supplied unix-millisecond `now`, so
+/// output stays deterministic under test.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum TimeMode {
+    #[default]
+    Absolute,
+    Relative(i64),
+}
+
+/// Divisor/suffix convention for `_bytes` rendering.
+///
+/// `Binary` (the default) divides by 1024 and labels with IEC suffixes
+/// (`KiB`/`MiB`/`GiB`/`TiB`); `Decimal` divides by 1000 and labels with SI
+/// suffixes (`KB`/`MB`/`GB`/`TB`). The crate used to always divide by 1024
+/// but label the result `KB`/`MB`, conflating the two conventions — `Binary`
+/// keeps the old rollover math but corrects the label.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ByteUnits {
+    #[default]
+    Binary,
+    Decimal,
+}
+
+// ═══════════════════════════════════════════
+// CLI helpers
+// ═══════════════════════════════════════════
+
+/// Parse a `--output` flag value into an [`OutputFormat`].
+pub fn cli_parse_output(s: &str) -> Result<OutputFormat, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "json" => Ok(OutputFormat::Json),
+        "yaml" => Ok(OutputFormat::Yaml),
+        "plain" => Ok(OutputFormat::Plain),
+        "query" => Ok(OutputFormat::Query),
+        other => Err(format!("--output: invalid value '{other}' (expected json, yaml, plain, or query)")),
     }
+}
 
-    /// Format a JSON value with pretty printing (JSON only; yaml/plain unchanged).
-    pub fn format_pretty(&self, value: &Value) -> String {
-        match self {
-            Self::Json => serde_json::to_string_pretty(value).unwrap_or_default(),
-            Self::Yaml => to_yaml(value),
-            Self::Plain => to_plain(value),
+/// Normalize `--log` filter values: trim, lowercase, dedup (first occurrence wins).
+pub fn cli_parse_log_filters<S: AsRef<str>>(filters: &[S]) -> Vec<String> {
+    let mut out = Vec::new();
+    for f in filters {
+        let normalized = f.as_ref().trim().to_ascii_lowercase();
+        if !normalized.is_empty() && !out.contains(&normalized) {
+            out.push(normalized);
         }
     }
+    out
+}
+
+/// Render a value in the requested [`OutputFormat`].
+pub fn cli_output(value: &Value, format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Json => output_json(value),
+        OutputFormat::Yaml => output_yaml(value),
+        OutputFormat::Plain => output_plain(value),
+        OutputFormat::Query => output_query(value),
+    }
+}
+
+/// Build a `{code: "error", error_code: "invalid_request", retryable: false, ...}`
+/// envelope for CLI argument errors (e.g. a failed `try_parse` or `cli_parse_output`).
+pub fn build_cli_error(message: &str) -> Value {
+    build_json(
+        "error",
+        serde_json::json!({
+            "error": message,
+            "error_code": "invalid_request",
+            "retryable": false,
+        }),
+        Some(serde_json::json!({"duration_ms": 0})),
+    )
 }
 
 // ═══════════════════════════════════════════
-// YAML
+// AFD Protocol templates
 // ═══════════════════════════════════════════
 
-/// Convert a JSON Value into a YAML document.
+/// Build `{code: "<code>", ...fields, trace?: ...}` — the base protocol envelope.
 ///
-/// Strings are always quoted to avoid YAML pitfalls (`no` → `false`, `3.0` → float).
-/// Values are preserved as-is — no suffix-driven transformation.
-/// Starts with `---` for multi-document streaming compatibility.
-pub fn to_yaml(value: &Value) -> String {
+/// `fields` must be an object; any non-object is dropped (only `code`/`trace`
+/// survive). `code` always wins over an identically-named field in `fields`,
+/// and an explicit `trace` always wins over a `trace` key already in `fields`.
+pub fn build_json(code: &str, fields: Value, trace: Option<Value>) -> Value {
+    let mut obj = match fields {
+        Value::Object(map) => map,
+        _ => serde_json::Map::new(),
+    };
+    obj.insert("code".to_string(), Value::String(code.to_string()));
+    if let Some(trace) = trace {
+        obj.insert("trace".to_string(), trace);
+    }
+    Value::Object(obj)
+}
+
+/// Build `{code: "ok", result: ..., trace?: ...}`.
+pub fn build_json_ok(result: Value, trace: Option<Value>) -> Value {
+    build_json("ok", serde_json::json!({"result": result}), trace)
+}
+
+/// Build `{code: "error", error: "message", trace?: ...}`.
+pub fn build_json_error(message: &str, trace: Option<Value>) -> Value {
+    build_json("error", serde_json::json!({"error": message}), trace)
+}
+
+// ═══════════════════════════════════════════
+// JSON — canonical, lossless
+// ═══════════════════════════════════════════
+
+/// Serialize a value as a single compact JSONL line, redacting `_secret` fields.
+/// Keys and raw numeric values are left untouched — JSON is the lossless format.
+pub fn output_json(value: &Value) -> String {
+    output_json_rules(value, &RuleSet::default())
+}
+
+/// Serialize a value as a single compact JSONL line, redacting `_secret`
+/// fields and any field matched by a `redact: true` rule in `rules`.
+pub fn output_json_rules(value: &Value, rules: &RuleSet) -> String {
+    let mut redacted = value.clone();
+    internal_redact_secrets_rules(&mut redacted, rules);
+    serde_json::to_string(&redacted).unwrap_or_default()
+}
+
+// ═══════════════════════════════════════════
+// YAML — suffix-stripped, multi-line
+// ═══════════════════════════════════════════
+
+/// Convert a value into a YAML document with absolute timestamps.
+pub fn output_yaml(value: &Value) -> String {
+    output_yaml_rules(value, TimeMode::Absolute, &RuleSet::default())
+}
+
+/// Convert a value into a YAML document, rendering epoch/RFC 3339 fields per `mode`.
+///
+/// Each key's semantic suffix (`_ms`, `_bytes`, `_secret`, ...) is stripped and
+/// the value is rendered as a quoted, human-readable string. Plain numbers,
+/// bools, and unsuffixed strings pass through with their usual YAML scalar
+/// form. Starts with `---` for multi-document streaming compatibility.
+pub fn output_yaml_mode(value: &Value, mode: TimeMode) -> String {
+    output_yaml_rules(value, mode, &RuleSet::default())
+}
+
+/// Convert a value into a YAML document per `mode`, consulting `rules` ahead
+/// of the built-in suffix table so user-defined suffixes (and overrides of
+/// built-in ones) take effect. See [`rules::RuleSet`].
+pub fn output_yaml_rules(value: &Value, mode: TimeMode, rules: &RuleSet) -> String {
+    output_yaml_full(value, mode, ByteUnits::default(), rules)
+}
+
+/// Convert a value into a YAML document with absolute timestamps, rendering
+/// `_bytes` fields per `units` (decimal `KB` or binary `KiB`).
+pub fn output_yaml_units(value: &Value, units: ByteUnits) -> String {
+    output_yaml_full(value, TimeMode::Absolute, units, &RuleSet::default())
+}
+
+/// Convert a value into a YAML document with absolute timestamps, consulting
+/// `registry` ahead of the built-in suffix table. See [`registry::SuffixRegistry`].
+pub fn output_yaml_registry(value: &Value, registry: &SuffixRegistry) -> String {
+    output_yaml_all(value, TimeMode::Absolute, ByteUnits::default(), &RuleSet::default(), registry)
+}
+
+/// Convert a value into a YAML document per `mode`/`units`, consulting
+/// `rules` ahead of the built-in suffix table. The most general YAML entry
+/// point with no custom suffix registry — every other `output_yaml*`
+/// function except [`output_yaml_all`] delegates here.
+pub fn output_yaml_full(value: &Value, mode: TimeMode, units: ByteUnits, rules: &RuleSet) -> String {
+    output_yaml_all(value, mode, units, rules, &SuffixRegistry::default())
+}
+
+/// Convert a value into a YAML document per `mode`/`units`, consulting
+/// `rules` then `registry` ahead of the built-in suffix table. The most
+/// general YAML entry point — every other `output_yaml*` function delegates
+/// here (with an empty `registry`).
+pub fn output_yaml_all(
+    value: &Value,
+    mode: TimeMode,
+    units: ByteUnits,
+    rules: &RuleSet,
+    registry: &SuffixRegistry,
+) -> String {
+    let mut redacted = value.clone();
+    internal_redact_secrets_rules(&mut redacted, rules);
     let mut lines = vec!["---".to_string()];
-    render_yaml(value, 0, &mut lines);
+    render_yaml(&redacted, 0, &mut lines, mode, units, rules, registry);
     lines.join("\n")
 }
 
-fn render_yaml(value: &Value, indent: usize, lines: &mut Vec<String>) {
+fn render_yaml(
+    value: &Value,
+    indent: usize,
+    lines: &mut Vec<String>,
+    mode: TimeMode,
+    units: ByteUnits,
+    rules: &RuleSet,
+    registry: &SuffixRegistry,
+) {
     let prefix = "  ".repeat(indent);
     match value {
         Value::Object(map) => {
+            let collisions = stripped_key_collisions(map, mode, units, rules, registry);
             for (k, v) in jcs_sorted(map) {
                 match v {
                     Value::Object(inner) if !inner.is_empty() => {
-                        lines.push(format!("{}{}:", prefix, k));
-                        render_yaml(v, indent + 1, lines);
+                        lines.push(format!("{prefix}{k}:"));
+                        render_yaml(v, indent + 1, lines, mode, units, rules, registry);
                     }
                     Value::Object(_) => {
-                        lines.push(format!("{}{}: {{}}", prefix, k));
+                        lines.push(format!("{prefix}{k}: {{}}"));
                     }
                     Value::Array(arr) => {
                         if arr.is_empty() {
-                            lines.push(format!("{}{}: []", prefix, k));
+                            lines.push(format!("{prefix}{k}: []"));
                         } else {
-                            lines.push(format!("{}{}:", prefix, k));
+                            lines.push(format!("{prefix}{k}:"));
                             for item in arr {
                                 if item.is_object() {
-                                    lines.push(format!("{}  -", prefix));
-                                    render_yaml(item, indent + 2, lines);
+                                    lines.push(format!("{prefix}  -"));
+                                    render_yaml(item, indent + 2, lines, mode, units, rules, registry);
                                 } else {
-                                    lines.push(format!("{}  - {}", prefix, yaml_scalar(item)));
+                                    lines.push(format!("{prefix}  - {}", yaml_scalar(item)));
                                 }
                             }
                         }
                     }
                     _ => {
-                        lines.push(format!("{}{}: {}", prefix, k, yaml_scalar(v)));
+                        let stripped = strip_and_format(k, v, mode, units, rules, registry)
+                            .filter(|(dk, _)| !collisions.contains(dk.as_ref()));
+                        let (display_key, rendered) = match stripped {
+                            Some((dk, formatted)) => (dk, quote_display(&formatted)),
+                            None => (CowStr::Borrowed(k.as_str()), yaml_scalar(v)),
+                        };
+                        lines.push(format!("{prefix}{display_key}: {rendered}"));
                     }
                 }
             }
         }
         _ => {
-            lines.push(format!("{}{}", prefix, yaml_scalar(value)));
+            lines.push(format!("{prefix}{}", yaml_scalar(value)));
         }
     }
 }
@@ -103,239 +328,457 @@ fn jcs_sorted(map: &serde_json::Map<String, Value>) -> Vec<(&String, &Value)> {
 
 fn yaml_scalar(value: &Value) -> String {
     match value {
-        Value::String(s) => {
-            let escaped = s
-                .replace('\\', "\\\\")
-                .replace('"', "\\\"")
-                .replace('\n', "\\n")
-                .replace('\r', "\\r")
-                .replace('\t', "\\t");
-            format!("\"{}\"", escaped)
-        }
+        Value::String(s) => quote_display(s),
         Value::Null => "null".to_string(),
         Value::Bool(b) => b.to_string(),
         Value::Number(n) => n.to_string(),
-        other => format!("\"{}\"", other.to_string().replace('"', "\\\"")),
+        other => quote_display(&other.to_string()),
     }
 }
 
+fn quote_display(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
 // ═══════════════════════════════════════════
-// Plain
+// Plain — suffix-stripped, single-line logfmt
 // ═══════════════════════════════════════════
 
-/// Convert a JSON Value into human-readable plain text.
+/// Convert a value into single-line logfmt with absolute timestamps.
+pub fn output_plain(value: &Value) -> String {
+    output_plain_rules(value, TimeMode::Absolute, &RuleSet::default())
+}
+
+/// Convert a value into single-line logfmt, rendering epoch/RFC 3339 fields per `mode`.
 ///
-/// Applies agent-first-data suffix-driven formatting:
-/// - `_ms` → append `ms`, or convert to seconds if ≥ 1000
-/// - `_bytes` → human-readable (`446.1KB`)
-/// - `_epoch_ms` → RFC 3339
-/// - `_secret` → `***`
-/// - Currency suffixes → formatted amounts
-pub fn to_plain(value: &Value) -> String {
-    let mut lines = Vec::new();
-    render_plain(value, 0, &mut lines);
-    lines.join("\n")
+/// Nested objects flatten into dot-notation keys (`trace.duration=1.28s`).
+/// Each leaf key's semantic suffix is stripped and the value humanized; values
+/// containing spaces are quoted, arrays are comma-joined, keys are sorted by
+/// their full dotted path for determinism.
+pub fn output_plain_mode(value: &Value, mode: TimeMode) -> String {
+    output_plain_rules(value, mode, &RuleSet::default())
 }
 
-fn render_plain(value: &Value, indent: usize, lines: &mut Vec<String>) {
-    let prefix = "  ".repeat(indent);
-    match value {
-        Value::Object(map) => {
-            for (k, v) in jcs_sorted(map) {
-                match v {
-                    Value::Object(_) => {
-                        lines.push(format!("{}{}:", prefix, k));
-                        render_plain(v, indent + 1, lines);
-                    }
-                    Value::Array(arr) => {
-                        if arr.is_empty() {
-                            lines.push(format!("{}{}: []", prefix, k));
-                        } else if arr.iter().all(|v| !v.is_object() && !v.is_array()) {
-                            lines.push(format!("{}{}:", prefix, k));
-                            for item in arr {
-                                lines.push(format!("{}  - {}", prefix, plain_scalar(item)));
-                            }
-                        } else {
-                            lines.push(format!("{}{}:", prefix, k));
-                            for item in arr {
-                                if item.is_object() {
-                                    lines.push(format!("{}  -", prefix));
-                                    render_plain(item, indent + 2, lines);
-                                } else {
-                                    lines.push(format!("{}  - {}", prefix, plain_scalar(item)));
-                                }
-                            }
-                        }
-                    }
-                    _ => {
-                        lines.push(format!("{}{}: {}", prefix, k, format_plain_field(k, v)));
-                    }
-                }
-            }
-        }
-        _ => {
-            lines.push(format!("{}{}", prefix, plain_scalar(value)));
-        }
-    }
+/// Convert a value into single-line logfmt per `mode`, consulting `rules`
+/// ahead of the built-in suffix table. See [`rules::RuleSet`].
+pub fn output_plain_rules(value: &Value, mode: TimeMode, rules: &RuleSet) -> String {
+    output_plain_full(value, mode, ByteUnits::default(), rules)
+}
+
+/// Convert a value into single-line logfmt with absolute timestamps,
+/// rendering `_bytes` fields per `units` (decimal `KB` or binary `KiB`).
+pub fn output_plain_units(value: &Value, units: ByteUnits) -> String {
+    output_plain_full(value, TimeMode::Absolute, units, &RuleSet::default())
+}
+
+/// Convert a value into single-line logfmt with absolute timestamps,
+/// consulting `registry` ahead of the built-in suffix table. See
+/// [`registry::SuffixRegistry`].
+pub fn output_plain_registry(value: &Value, registry: &SuffixRegistry) -> String {
+    output_plain_all(value, TimeMode::Absolute, ByteUnits::default(), &RuleSet::default(), registry)
+}
+
+/// Convert a value into single-line logfmt per `mode`/`units`, consulting
+/// `rules` ahead of the built-in suffix table. The most general logfmt entry
+/// point with no custom suffix registry — every other `output_plain*`
+/// function except [`output_plain_all`] delegates here.
+pub fn output_plain_full(value: &Value, mode: TimeMode, units: ByteUnits, rules: &RuleSet) -> String {
+    output_plain_all(value, mode, units, rules, &SuffixRegistry::default())
+}
+
+/// Convert a value into single-line logfmt per `mode`/`units`, consulting
+/// `rules` then `registry` ahead of the built-in suffix table. The most
+/// general logfmt entry point — every other `output_plain*` function
+/// delegates here (with an empty `registry`).
+pub fn output_plain_all(
+    value: &Value,
+    mode: TimeMode,
+    units: ByteUnits,
+    rules: &RuleSet,
+    registry: &SuffixRegistry,
+) -> String {
+    let mut redacted = value.clone();
+    internal_redact_secrets_rules(&mut redacted, rules);
+    let mut pairs = Vec::new();
+    flatten_plain(&redacted, String::new(), &mut pairs, mode, units, rules, registry, true);
+    pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+    pairs
+        .into_iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
-/// Format a scalar value for plain output, applying suffix-driven rules.
+/// Convert a value into a percent-encoded URL query string (`key=val&key2=val2`).
 ///
-/// Suffix priority (most specific first):
-/// 1. `_secret` → `***`
-/// 2. `_epoch_ms` / `_epoch_s` / `_epoch_ns` → RFC 3339
-/// 3. `_rfc3339` → pass through
-/// 4. `_bytes` → human-readable size
-/// 5. Currency: `_msats`, `_sats`, `_btc`, `_usd_cents`, `_eur_cents`, `_cents`, `_jpy`
-/// 6. Duration: `_minutes`, `_hours`, `_days`, `_ms`, `_ns`, `_us`, `_s`
-fn format_plain_field(key: &str, value: &Value) -> String {
-    let lower = key.to_ascii_lowercase();
+/// Reuses [`output_plain`]'s dot-notation flattening (`trace.duration=...`),
+/// comma-joined arrays, sorted keys, secret redaction, and semantic value
+/// formatting (`duration=1.28s`, `size=5.0MiB`) — the only difference is that
+/// reserved characters in keys and values are percent-encoded instead of
+/// space-quoted, and pairs are joined with `&` instead of a space.
+pub fn output_query(value: &Value) -> String {
+    let mut redacted = value.clone();
+    internal_redact_secrets(&mut redacted);
+    let mut pairs = Vec::new();
+    flatten_plain(
+        &redacted,
+        String::new(),
+        &mut pairs,
+        TimeMode::Absolute,
+        ByteUnits::default(),
+        &RuleSet::default(),
+        &SuffixRegistry::default(),
+        false,
+    );
+    pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+    pairs
+        .into_iter()
+        .map(|(k, v)| format!("{}={}", percent_encode(&k), percent_encode(&v)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
 
-    // Secret — always redact
-    if lower.ends_with("_secret") {
-        return "***".to_string();
+#[allow(clippy::too_many_arguments)]
+fn flatten_plain(
+    value: &Value,
+    prefix: String,
+    out: &mut Vec<(String, String)>,
+    mode: TimeMode,
+    units: ByteUnits,
+    rules: &RuleSet,
+    registry: &SuffixRegistry,
+    quote_spaces: bool,
+) {
+    let Value::Object(map) = value else { return };
+    let collisions = stripped_key_collisions(map, mode, units, rules, registry);
+    for (k, v) in map {
+        match v {
+            Value::Object(_) => {
+                flatten_plain(v, join_path(&prefix, k), out, mode, units, rules, registry, quote_spaces)
+            }
+            _ => {
+                let stripped = strip_and_format(k, v, mode, units, rules, registry)
+                    .filter(|(dk, _)| !collisions.contains(dk.as_ref()));
+                let (leaf_key, rendered) = match stripped {
+                    Some((dk, formatted)) => (dk, formatted),
+                    None => (CowStr::Borrowed(k.as_str()), plain_value(v, quote_spaces)),
+                };
+                out.push((join_path(&prefix, &leaf_key), rendered));
+            }
+        }
     }
+}
 
-    // Timestamps → RFC 3339
-    if lower.ends_with("_epoch_ms") {
-        if let Some(ms) = value.as_i64() {
-            return format_rfc3339_ms(ms);
-        }
+fn join_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
     }
-    if lower.ends_with("_epoch_s") {
-        if let Some(s) = value.as_i64() {
-            return format_rfc3339_ms(s * 1000);
-        }
+}
+
+fn plain_value(value: &Value, quote_spaces: bool) -> String {
+    match value {
+        Value::String(s) if quote_spaces && s.contains(' ') => format!("\"{s}\""),
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::Array(arr) => arr.iter().map(|v| plain_value(v, quote_spaces)).collect::<Vec<_>>().join(","),
+        Value::Object(_) => String::new(),
     }
-    if lower.ends_with("_epoch_ns") {
-        if let Some(ns) = value.as_i64() {
-            return format_rfc3339_ms(ns.div_euclid(1_000_000));
+}
+
+/// Percent-encode every byte outside the RFC 3986 unreserved set
+/// (`ALPHA / DIGIT / "-" / "." / "_" / "~"`).
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{byte:02X}")),
         }
     }
-    if lower.ends_with("_rfc3339") {
-        return plain_scalar(value);
+    out
+}
+
+/// Plain scalar: no quotes, raw value.
+pub(crate) fn plain_scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        other => other.to_string(),
     }
+}
 
-    // Size
-    if lower.ends_with("_bytes") {
-        if let Some(n) = value.as_i64() {
-            return format_bytes_human(n);
+// ═══════════════════════════════════════════
+// Suffix-driven key stripping + value formatting
+// (shared by output_yaml and output_plain)
+// ═══════════════════════════════════════════
+
+/// Find every stripped display key that two or more sibling scalar fields in
+/// `map` would collapse onto, so the caller can keep those fields' original
+/// keys and raw values instead of silently merging them.
+fn stripped_key_collisions(
+    map: &serde_json::Map<String, Value>,
+    mode: TimeMode,
+    units: ByteUnits,
+    rules: &RuleSet,
+    registry: &SuffixRegistry,
+) -> BTreeSet<String> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for (k, v) in map {
+        if !v.is_object() {
+            if let Some((dk, _)) = strip_and_format(k, v, mode, units, rules, registry) {
+                *counts.entry(dk.into_owned()).or_insert(0) += 1;
+            }
         }
     }
+    counts.into_iter().filter(|(_, n)| *n > 1).map(|(k, _)| k).collect()
+}
 
-    // Percentage
-    if lower.ends_with("_percent") {
+/// Match `key`'s trailing suffix, honoring only an all-lowercase or
+/// all-uppercase suffix (mixed case, e.g. `_Secret`, never matches).
+pub(crate) fn match_suffix<'a>(key: &'a str, suffix: &str) -> Option<&'a str> {
+    key.strip_suffix(suffix)
+        .or_else(|| key.strip_suffix(suffix.to_ascii_uppercase().as_str()))
+}
+
+/// Strip a key's semantic suffix and render a humanized value, if the suffix
+/// is recognized and `value`'s type supports that suffix's conversion.
+/// Returns `None` when there's no matching suffix or the value's type can't
+/// be converted — callers fall back to the original key and a raw scalar.
+///
+/// `rules` is consulted first, then `registry`, so a user-defined suffix —
+/// new or one that shadows a built-in — always wins; see [`rules::RuleSet`]
+/// and [`registry::SuffixRegistry`].
+///
+/// Suffix priority (most specific first): `_secret`; `_epoch_ms`/`_epoch_s`/
+/// `_epoch_ns`; `_rfc3339`; `_bytes`; `_percent`; `_msats`/`_sats`/`_btc`;
+/// `_usd_cents`/`_eur_cents`/`_jpy`/generic `_{code}_cents`; `_minutes`/
+/// `_hours`/`_days`; `_ms`; `_ns`/`_us`/`_s`.
+fn strip_and_format<'a>(
+    key: &'a str,
+    value: &Value,
+    mode: TimeMode,
+    units: ByteUnits,
+    rules: &RuleSet,
+    registry: &SuffixRegistry,
+) -> Option<(CowStr<'a>, String)> {
+    if let Some(stripped) = rules.strip_and_format(key, value, units) {
+        return Some(stripped);
+    }
+    if let Some(stripped) = registry.lookup(key, value) {
+        return Some(stripped);
+    }
+    if let Some(rest) = match_suffix(key, "_secret") {
+        return Some((CowStr::Borrowed(rest), "***".to_string()));
+    }
+    if let Some(rest) = match_suffix(key, "_epoch_ms") {
+        let ms = value.as_i64()?;
+        return Some((CowStr::Borrowed(rest), format_epoch(ms, mode)));
+    }
+    if let Some(rest) = match_suffix(key, "_epoch_s") {
+        let s = value.as_i64()?;
+        return Some((CowStr::Borrowed(rest), format_epoch(s * 1000, mode)));
+    }
+    if let Some(rest) = match_suffix(key, "_epoch_ns") {
+        let ns = value.as_i64()?;
+        return Some((CowStr::Borrowed(rest), format_epoch(ns.div_euclid(1_000_000), mode)));
+    }
+    if let Some(rest) = match_suffix(key, "_rfc3339") {
+        return Some((CowStr::Borrowed(rest), format_rfc3339_field(value, mode)));
+    }
+    if let Some(rest) = match_suffix(key, "_bytes") {
+        let n = value.as_i64()?;
+        return Some((CowStr::Borrowed(rest), format_bytes_human(n, units)));
+    }
+    if let Some(rest) = match_suffix(key, "_percent") {
         if value.is_number() {
-            return format!("{}%", plain_scalar(value));
+            return Some((CowStr::Borrowed(rest), format!("{}%", plain_scalar(value))));
         }
+        return None;
     }
-
-    // Currency — Bitcoin
-    if lower.ends_with("_msats") {
+    if let Some(rest) = match_suffix(key, "_msats") {
         if value.is_number() {
-            return format!("{}msats", plain_scalar(value));
+            return Some((CowStr::Borrowed(rest), format!("{}msats", plain_scalar(value))));
         }
+        return None;
     }
-    if lower.ends_with("_sats") {
+    if let Some(rest) = match_suffix(key, "_sats") {
         if value.is_number() {
-            return format!("{}sats", plain_scalar(value));
+            return Some((CowStr::Borrowed(rest), format!("{}sats", plain_scalar(value))));
         }
+        return None;
     }
-    if lower.ends_with("_btc") {
+    if let Some(rest) = match_suffix(key, "_btc") {
         if value.is_number() {
-            return format!("{} BTC", plain_scalar(value));
+            return Some((CowStr::Borrowed(rest), format!("{} BTC", plain_scalar(value))));
         }
+        return None;
     }
-
-    // Currency — Fiat with symbol
-    if lower.ends_with("_usd_cents") {
-        if let Some(n) = value.as_u64() {
-            return format!("${}.{:02}", n / 100, n % 100);
-        }
+    if let Some(rest) = match_suffix(key, "_usd_cents") {
+        let n = value.as_u64()?;
+        return Some((CowStr::Borrowed(rest), format!("${}.{:02}", n / 100, n % 100)));
     }
-    if lower.ends_with("_eur_cents") {
-        if let Some(n) = value.as_u64() {
-            return format!("€{}.{:02}", n / 100, n % 100);
-        }
+    if let Some(rest) = match_suffix(key, "_eur_cents") {
+        let n = value.as_u64()?;
+        return Some((CowStr::Borrowed(rest), format!("€{}.{:02}", n / 100, n % 100)));
     }
-    if lower.ends_with("_jpy") {
-        if let Some(n) = value.as_u64() {
-            return format!("¥{}", format_with_commas(n));
-        }
+    if let Some(rest) = match_suffix(key, "_jpy") {
+        let n = value.as_u64()?;
+        return Some((CowStr::Borrowed(rest), format!("¥{}", format_with_commas(n))));
     }
-    // Currency — Generic _{code}_cents
-    if lower.ends_with("_cents") {
-        if let Some(code) = extract_currency_code(&lower) {
-            if let Some(n) = value.as_u64() {
-                return format!("{}.{:02} {}", n / 100, n % 100, code.to_uppercase());
-            }
-        }
+    if let Some(rest) = match_suffix(key, "_cents") {
+        let lower = key.to_ascii_lowercase();
+        let code = currency_code_from_key(&lower)?;
+        let n = value.as_u64()?;
+        let last_underscore = rest.rfind('_')?;
+        return Some((
+            CowStr::Borrowed(&rest[..last_underscore]),
+            format!("{}.{:02} {}", n / 100, n % 100, code.to_uppercase()),
+        ));
     }
-
-    // Duration — long units (check before short)
-    if lower.ends_with("_minutes") {
+    if let Some(rest) = match_suffix(key, "_minutes") {
         if value.is_number() {
-            return format!("{} minutes", plain_scalar(value));
+            return Some((CowStr::Borrowed(rest), format!("{} minutes", plain_scalar(value))));
         }
+        return None;
     }
-    if lower.ends_with("_hours") {
+    if let Some(rest) = match_suffix(key, "_hours") {
         if value.is_number() {
-            return format!("{} hours", plain_scalar(value));
+            return Some((CowStr::Borrowed(rest), format!("{} hours", plain_scalar(value))));
         }
+        return None;
     }
-    if lower.ends_with("_days") {
+    if let Some(rest) = match_suffix(key, "_days") {
         if value.is_number() {
-            return format!("{} days", plain_scalar(value));
+            return Some((CowStr::Borrowed(rest), format!("{} days", plain_scalar(value))));
         }
+        return None;
     }
-
-    // Duration — ms (with ≥1000 → seconds conversion)
-    if lower.ends_with("_ms") && !lower.ends_with("_epoch_ms") {
-        if let Some(n) = value.as_u64() {
-            return if n >= 1000 {
-                format!("{:.2}s", n as f64 / 1000.0)
-            } else {
-                format!("{}ms", n)
-            };
-        }
-        if let Some(n) = value.as_f64() {
-            return if n >= 1000.0 {
-                format!("{:.2}s", n / 1000.0)
-            } else {
-                format!("{}ms", plain_scalar(value))
-            };
-        }
+    if let Some(rest) = match_suffix(key, "_ms") {
+        return format_duration_ms(value).map(|formatted| (CowStr::Borrowed(rest), formatted));
     }
-
-    // Duration — ns, us, s
-    if lower.ends_with("_ns") && !lower.ends_with("_epoch_ns") {
+    if let Some(rest) = match_suffix(key, "_ns") {
         if value.is_number() {
-            return format!("{}ns", plain_scalar(value));
+            return Some((CowStr::Borrowed(rest), format!("{}ns", plain_scalar(value))));
         }
+        return None;
     }
-    if lower.ends_with("_us") {
+    if let Some(rest) = match_suffix(key, "_us") {
         if value.is_number() {
-            return format!("{}μs", plain_scalar(value));
+            return Some((CowStr::Borrowed(rest), format!("{}μs", plain_scalar(value))));
         }
+        return None;
     }
-    if lower.ends_with("_s") && !lower.ends_with("_epoch_s") {
+    if let Some(rest) = match_suffix(key, "_s") {
         if value.is_number() {
-            return format!("{}s", plain_scalar(value));
+            return Some((CowStr::Borrowed(rest), format!("{}s", plain_scalar(value))));
         }
+        return None;
     }
+    None
+}
 
-    // Default — no transformation
-    plain_scalar(value)
+/// Render a millisecond value the way the built-in `_ms` suffix does, and
+/// the way a [`rules::FormatStrategy::Duration`] rule does: `"{n}ms"` under
+/// 1000, `"{seconds}s"` at or above it.
+pub(crate) fn format_duration_ms(value: &Value) -> Option<String> {
+    if let Some(n) = value.as_u64() {
+        return Some(if n >= 1000 {
+            format!("{}s", format_seconds(n as f64 / 1000.0))
+        } else {
+            format!("{n}ms")
+        });
+    }
+    if let Some(n) = value.as_f64() {
+        return Some(if n >= 1000.0 {
+            format!("{}s", format_seconds(n / 1000.0))
+        } else {
+            format!("{}ms", plain_scalar(value))
+        });
+    }
+    None
 }
 
-/// Plain scalar: no quotes, raw value.
-fn plain_scalar(value: &Value) -> String {
-    match value {
-        Value::String(s) => s.clone(),
-        Value::Null => "null".to_string(),
-        Value::Bool(b) => b.to_string(),
-        Value::Number(n) => n.to_string(),
-        other => other.to_string(),
+/// Render `n as f64 / 1000.0`-style seconds with the shortest representation
+/// that still shows at least one decimal (`1.0s`, not `1s`; `1.28s`; `1.001s`).
+fn format_seconds(n: f64) -> String {
+    let mut s = format!("{n}");
+    if !s.contains('.') {
+        s.push_str(".0");
+    }
+    s
+}
+
+fn format_epoch(ms: i64, mode: TimeMode) -> String {
+    match mode {
+        TimeMode::Absolute => format_rfc3339_ms(ms),
+        TimeMode::Relative(now_ms) => humanize_delta((now_ms - ms).div_euclid(1000)),
+    }
+}
+
+fn format_rfc3339_field(value: &Value, mode: TimeMode) -> String {
+    match mode {
+        TimeMode::Absolute => plain_scalar(value),
+        TimeMode::Relative(now_ms) => {
+            if let Some(parsed) = value.as_str().and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok()) {
+                return humanize_delta((now_ms - parsed.timestamp_millis()).div_euclid(1000));
+            }
+            plain_scalar(value)
+        }
+    }
+}
+
+/// Cascading units for [`humanize_delta`], seconds-per-unit, smallest first.
+const RELATIVE_UNITS: [(&str, i64); 7] = [
+    ("s", 1),
+    ("m", 60),
+    ("h", 3600),
+    ("d", 86_400),
+    ("w", 604_800),
+    ("mo", 2_629_746),  // 30.44 days
+    ("y", 31_557_600),  // 365.25 days
+];
+
+/// Humanize a signed delta in seconds as "Xu ago" / "in Xu", with one
+/// secondary unit when it's nonzero ("1h 5m ago"). Deltas under 2s in
+/// magnitude render as "just now".
+fn humanize_delta(delta_secs: i64) -> String {
+    let abs = delta_secs.unsigned_abs();
+    if abs < 2 {
+        return "just now".to_string();
+    }
+    let mut idx = 0;
+    for (i, (_, secs)) in RELATIVE_UNITS.iter().enumerate() {
+        if abs >= *secs as u64 {
+            idx = i;
+        }
+    }
+    let (unit, secs) = RELATIVE_UNITS[idx];
+    let major = abs / secs as u64;
+    let mut rendered = format!("{major}{unit}");
+    if idx > 0 {
+        let (minor_unit, minor_secs) = RELATIVE_UNITS[idx - 1];
+        let minor = (abs % secs as u64) / minor_secs as u64;
+        if minor > 0 {
+            rendered.push(' ');
+            rendered.push_str(&format!("{minor}{minor_unit}"));
+        }
+    }
+    if delta_secs >= 0 {
+        format!("{rendered} ago")
+    } else {
+        format!("in {rendered}")
     }
 }
 
@@ -343,74 +786,98 @@ fn plain_scalar(value: &Value) -> String {
 // Secret redaction
 // ═══════════════════════════════════════════
 
-/// Walk a JSON Value tree and redact any field ending in `_secret`.
+/// How a redacted secret's replacement value is rendered.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RedactMode {
+    /// Replace the value with `"***"` — hides it completely.
+    #[default]
+    Full,
+    /// Reveal a few leading/trailing characters (`"sk-1***cdef"`), masking
+    /// the middle. Falls back to [`RedactMode::Full`] when the value's
+    /// string form is too short (≤ 8 chars) to safely reveal any edge.
+    Partial,
+    /// Replace the value with the first 8 hex chars of its SHA-256 digest,
+    /// so agents can tell whether two redacted fields held the same secret
+    /// without ever seeing it.
+    Fingerprint,
+}
+
+/// Walk a JSON Value tree and redact any field ending in `_secret`/`_SECRET`.
 ///
-/// Applies the AFD convention: `_secret` suffix signals sensitive data.
 /// String values are replaced with `"***"`. Call this before serializing
-/// config or log output in any format (JSON, YAML, plain).
-pub fn redact_secrets(value: &mut Value) {
+/// config or log output, or rely on [`output_json`]/[`output_yaml`]/
+/// [`output_plain`]/[`output_query`], which already apply it.
+pub fn internal_redact_secrets(value: &mut Value) {
+    internal_redact_secrets_all(value, &RuleSet::default(), RedactMode::default())
+}
+
+/// Walk a JSON Value tree and redact any field ending in `_secret`/`_SECRET`,
+/// rendering the replacement per `mode`. See [`RedactMode`].
+pub fn internal_redact_secrets_mode(value: &mut Value, mode: RedactMode) {
+    internal_redact_secrets_all(value, &RuleSet::default(), mode)
+}
+
+/// Walk a JSON Value tree and redact any field ending in `_secret`/`_SECRET`,
+/// or matched by a `redact: true` rule in `rules`.
+pub fn internal_redact_secrets_rules(value: &mut Value, rules: &RuleSet) {
+    internal_redact_secrets_all(value, rules, RedactMode::default())
+}
+
+/// Walk a JSON Value tree and redact any field ending in `_secret`/`_SECRET`,
+/// or matched by a `redact: true` rule in `rules`, rendering each
+/// replacement per `mode`. The most general redaction entry point — every
+/// other `internal_redact_secrets*` function delegates here.
+pub fn internal_redact_secrets_all(value: &mut Value, rules: &RuleSet, mode: RedactMode) {
     match value {
         Value::Object(map) => {
             let secret_keys: Vec<String> = map
                 .keys()
-                .filter(|k| k.to_ascii_lowercase().ends_with("_secret"))
+                .filter(|k| match_suffix(k, "_secret").is_some() || rules.is_redacted(k))
                 .cloned()
                 .collect();
             for key in secret_keys {
-                if let Some(Value::String(s)) = map.get_mut(&key) {
-                    *s = "***".into();
+                if let Some(v) = map.get_mut(&key) {
+                    *v = redact_value(v, mode);
                 }
             }
             for v in map.values_mut() {
-                redact_secrets(v);
+                internal_redact_secrets_all(v, rules, mode);
             }
         }
         Value::Array(arr) => {
             for v in arr {
-                redact_secrets(v);
+                internal_redact_secrets_all(v, rules, mode);
             }
         }
         _ => {}
     }
 }
 
-// ═══════════════════════════════════════════
-// AFD Protocol templates
-// ═══════════════════════════════════════════
-
-/// Build `{code: "ok", result: ...}`.
-pub fn ok(result: Value) -> Value {
-    serde_json::json!({"code": "ok", "result": result})
-}
-
-/// Build `{code: "ok", result: ..., trace: ...}`.
-pub fn ok_trace(result: Value, trace: Value) -> Value {
-    serde_json::json!({"code": "ok", "result": result, "trace": trace})
-}
-
-/// Build `{code: "error", error: "message"}`.
-pub fn error(message: &str) -> Value {
-    serde_json::json!({"code": "error", "error": message})
-}
-
-/// Build `{code: "error", error: "message", trace: ...}`.
-pub fn error_trace(message: &str, trace: Value) -> Value {
-    serde_json::json!({"code": "error", "error": message, "trace": trace})
-}
+/// Render a single secret value's replacement per `mode`.
+fn redact_value(value: &Value, mode: RedactMode) -> Value {
+    const MASK: &str = "***";
+    const EDGE: usize = 4;
 
-/// Build `{code: "startup", config: ..., args: ..., env: ...}`.
-pub fn startup(config: Value, args: Value, env: Value) -> Value {
-    serde_json::json!({"code": "startup", "config": config, "args": args, "env": env})
-}
-
-/// Build `{code: "<custom>", ...fields}` — tool-defined status line.
-pub fn status(code: &str, fields: Value) -> Value {
-    let mut obj = match fields {
-        Value::Object(map) => map,
-        _ => serde_json::Map::new(),
-    };
-    obj.insert("code".to_string(), Value::String(code.to_string()));
-    Value::Object(obj)
+    match mode {
+        RedactMode::Full => Value::String(MASK.to_string()),
+        RedactMode::Partial => {
+            let s = plain_scalar(value);
+            let chars: Vec<char> = s.chars().collect();
+            if chars.len() <= 2 * EDGE {
+                Value::String(MASK.to_string())
+            } else {
+                let prefix: String = chars[..EDGE].iter().collect();
+                let suffix: String = chars[chars.len() - EDGE..].iter().collect();
+                Value::String(format!("{prefix}{MASK}{suffix}"))
+            }
+        }
+        RedactMode::Fingerprint => {
+            use sha2::{Digest, Sha256};
+            let digest = Sha256::digest(plain_scalar(value).as_bytes());
+            let fingerprint: String = digest.iter().take(4).map(|b| format!("{b:02x}")).collect();
+            Value::String(fingerprint)
+        }
+    }
 }
 
 // ═══════════════════════════════════════════
@@ -430,30 +897,35 @@ fn format_rfc3339_ms(ms: i64) -> String {
     }
 }
 
-/// Format bytes as human-readable size (binary units). Handles negative values.
-fn format_bytes_human(bytes: i64) -> String {
-    const KB: f64 = 1024.0;
-    const MB: f64 = KB * 1024.0;
-    const GB: f64 = MB * 1024.0;
-    const TB: f64 = GB * 1024.0;
+/// Format bytes as a human-readable size per `units` (decimal `KB`/1000 or
+/// binary `KiB`/1024). Handles negative values.
+pub(crate) fn format_bytes_human(bytes: i64, units: ByteUnits) -> String {
+    let (base, suffixes) = match units {
+        ByteUnits::Decimal => (1000.0, ["KB", "MB", "GB", "TB"]),
+        ByteUnits::Binary => (1024.0, ["KiB", "MiB", "GiB", "TiB"]),
+    };
+    let kb = base;
+    let mb = kb * base;
+    let gb = mb * base;
+    let tb = gb * base;
 
     let sign = if bytes < 0 { "-" } else { "" };
     let b = (bytes as f64).abs();
-    if b >= TB {
-        format!("{sign}{:.1}TB", b / TB)
-    } else if b >= GB {
-        format!("{sign}{:.1}GB", b / GB)
-    } else if b >= MB {
-        format!("{sign}{:.1}MB", b / MB)
-    } else if b >= KB {
-        format!("{sign}{:.1}KB", b / KB)
+    if b >= tb {
+        format!("{sign}{:.1}{}", b / tb, suffixes[3])
+    } else if b >= gb {
+        format!("{sign}{:.1}{}", b / gb, suffixes[2])
+    } else if b >= mb {
+        format!("{sign}{:.1}{}", b / mb, suffixes[1])
+    } else if b >= kb {
+        format!("{sign}{:.1}{}", b / kb, suffixes[0])
     } else {
         format!("{bytes}B")
     }
 }
 
 /// Format a number with thousands separators.
-fn format_with_commas(n: u64) -> String {
+pub(crate) fn format_with_commas(n: u64) -> String {
     let s = n.to_string();
     let mut result = String::with_capacity(s.len() + s.len() / 3);
     for (i, c) in s.chars().enumerate() {
@@ -465,53 +937,434 @@ fn format_with_commas(n: u64) -> String {
     result
 }
 
-/// Extract currency code from a `_{code}_cents` suffix.
+/// Extract currency code from a `_{code}_cents` key suffix.
 /// e.g., "fare_thb_cents" → Some("thb")
-fn extract_currency_code(key: &str) -> Option<&str> {
+fn currency_code_from_key(key: &str) -> Option<&str> {
     let without_cents = key.strip_suffix("_cents")?;
     let last_underscore = without_cents.rfind('_')?;
     Some(&without_cents[last_underscore + 1..])
 }
 
+// ═══════════════════════════════════════════
+// Inverse parsing — reconstruct canonical JSON from output_plain/output_yaml
+// ═══════════════════════════════════════════
+
+/// Parse `output_plain` logfmt back into canonical JSON.
+///
+/// Dotted keys (`trace.duration_ms=1.28s`) re-nest into objects. Values are
+/// split on unquoted spaces; a `"quoted value"` keeps embedded spaces intact.
+/// Bare values coerce to bool/number/null, comma-separated bare values become
+/// an array, and otherwise-ambiguous strings are checked against the same
+/// suffix table [`strip_and_format`] draws from — see [`reverse_suffix`] — so
+/// `duration=1.28s` reconstructs as `duration_ms: 1280`. Unrecognized strings
+/// pass through unchanged, same key, same value.
+///
+/// A bare `_bytes` value like `5.0MB` is ambiguous on its own — `KB`/`MB`/
+/// `GB`/`TB` could be [`ByteUnits::Decimal`] (1000) or [`ByteUnits::Binary`]
+/// (1024); this assumes [`ByteUnits::default`]. Use [`parse_plain_units`] if
+/// the document was produced with an explicit, non-default base.
+/// `KiB`/`MiB`/`GiB`/`TiB` are unambiguous and always parse as binary.
+pub fn parse_plain(s: &str) -> Value {
+    parse_plain_units(s, ByteUnits::default())
+}
+
+/// Parse `output_plain`/`output_plain_units` logfmt back into canonical
+/// JSON, resolving ambiguous `_bytes` suffixes (`KB`/`MB`/`GB`/`TB`) per
+/// `units` — the base the document was rendered with. See [`parse_plain`].
+pub fn parse_plain_units(s: &str, units: ByteUnits) -> Value {
+    let mut pairs = Vec::new();
+    for token in split_unquoted_spaces(s) {
+        if let Some(eq) = token.find('=') {
+            pairs.push(parse_plain_pair(&token[..eq], &token[eq + 1..], units));
+        }
+    }
+    nest_dotted(pairs)
+}
+
+fn split_unquoted_spaces(s: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ' ' if !in_quotes => {
+                if i > start {
+                    out.push(&s[start..i]);
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < s.len() {
+        out.push(&s[start..]);
+    }
+    out
+}
+
+fn parse_plain_pair(key: &str, raw: &str, units: ByteUnits) -> (String, Value) {
+    if let Some(inner) = raw.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        return (key.to_string(), Value::String(inner.to_string()));
+    }
+    if raw.is_empty() {
+        return (key.to_string(), Value::Null);
+    }
+    parse_unquoted_pair(key, raw, units)
+}
+
+/// Coerce an unquoted plain value: bool, number, a recognized humanized
+/// suffix value (reconstructing the suffix onto `key`, see [`reverse_suffix`]),
+/// a comma-joined array, or a passthrough string.
+fn parse_unquoted_pair(key: &str, raw: &str, units: ByteUnits) -> (String, Value) {
+    match raw {
+        "true" => return (key.to_string(), Value::Bool(true)),
+        "false" => return (key.to_string(), Value::Bool(false)),
+        "null" => return (key.to_string(), Value::Null),
+        _ => {}
+    }
+    if let Ok(n) = raw.parse::<i64>() {
+        return (key.to_string(), Value::Number(n.into()));
+    }
+    if let Ok(f) = raw.parse::<f64>() {
+        if let Some(n) = serde_json::Number::from_f64(f) {
+            return (key.to_string(), Value::Number(n));
+        }
+    }
+    if let Some((suffix, value)) = reverse_suffix(raw, units) {
+        return (format!("{key}{suffix}"), value);
+    }
+    if raw.contains(',') {
+        let items = raw.split(',').map(|part| parse_unquoted_pair("", part, units).1).collect();
+        return (key.to_string(), Value::Array(items));
+    }
+    (key.to_string(), Value::String(raw.to_string()))
+}
+
+/// Coerce a bare (unquoted) YAML scalar — no suffix reconstruction, since
+/// `output_yaml` always quotes humanized values via `quote_display`.
+fn parse_bare_scalar(raw: &str, units: ByteUnits) -> Value {
+    parse_unquoted_pair("", raw, units).1
+}
+
+/// Invert a humanized suffix value back to `(suffix, raw value)`, covering
+/// the round-trippable subset of [`strip_and_format`]: `_secret` (stays
+/// redacted, not un-redacted), `_bytes`, `_usd_cents`, `_epoch_ms` (from an
+/// RFC 3339 timestamp), and `_ms`/`_s`/`_ns`/`_us` durations. A bare `"Ns"`
+/// with a decimal point came from a `_ms` value of 1000 or more
+/// (`format_seconds` always keeps a decimal); without one, it's a raw `_s`
+/// integer.
+///
+/// `units` resolves the otherwise-ambiguous `_bytes` base (`KB`/`MB`/`GB`/
+/// `TB` could be decimal or binary); see [`reverse_bytes`].
+fn reverse_suffix(raw: &str, units: ByteUnits) -> Option<(&'static str, Value)> {
+    if raw == "***" {
+        return Some(("_secret", Value::String("***".to_string())));
+    }
+    if let Some((suffix, value)) = reverse_duration(raw) {
+        return Some((suffix, value));
+    }
+    if let Some(value) = reverse_bytes(raw, units) {
+        return Some(("_bytes", value));
+    }
+    if let Some(value) = reverse_usd_cents(raw) {
+        return Some(("_usd_cents", value));
+    }
+    if let Ok(parsed) = chrono::DateTime::parse_from_rfc3339(raw) {
+        return Some(("_epoch_ms", Value::Number(parsed.timestamp_millis().into())));
+    }
+    None
+}
+
+fn reverse_duration(raw: &str) -> Option<(&'static str, Value)> {
+    if let Some(num) = raw.strip_suffix("ms") {
+        return Some(("_ms", number_or_float(num.parse().ok()?)));
+    }
+    if let Some(num) = raw.strip_suffix("ns") {
+        return Some(("_ns", number_or_float(num.parse().ok()?)));
+    }
+    if let Some(num) = raw.strip_suffix("μs") {
+        return Some(("_us", number_or_float(num.parse().ok()?)));
+    }
+    if let Some(num) = raw.strip_suffix('s') {
+        let n: f64 = num.parse().ok()?;
+        if num.contains('.') {
+            return Some(("_ms", Value::Number(round_half_away_from_zero(n * 1000.0).into())));
+        }
+        return Some(("_s", number_or_float(n)));
+    }
+    None
+}
+
+/// Invert a `_bytes` value. `KiB`/`MiB`/`GiB`/`TiB` are unambiguous (always
+/// base 1024); `KB`/`MB`/`GB`/`TB` are not — `5.0MB` alone doesn't say
+/// whether it came from [`ByteUnits::Decimal`] (1000) or [`ByteUnits::Binary`]
+/// (1024), so the caller-supplied `units` picks the base for those.
+fn reverse_bytes(raw: &str, units: ByteUnits) -> Option<Value> {
+    let binary_base = 1024i64;
+    let ambiguous_base = match units {
+        ByteUnits::Decimal => 1000i64,
+        ByteUnits::Binary => 1024i64,
+    };
+    let (num_str, mult) = if let Some(n) = raw.strip_suffix("TiB") {
+        (n, binary_base.pow(4))
+    } else if let Some(n) = raw.strip_suffix("GiB") {
+        (n, binary_base.pow(3))
+    } else if let Some(n) = raw.strip_suffix("MiB") {
+        (n, binary_base.pow(2))
+    } else if let Some(n) = raw.strip_suffix("KiB") {
+        (n, binary_base)
+    } else if let Some(n) = raw.strip_suffix("TB") {
+        (n, ambiguous_base.pow(4))
+    } else if let Some(n) = raw.strip_suffix("GB") {
+        (n, ambiguous_base.pow(3))
+    } else if let Some(n) = raw.strip_suffix("MB") {
+        (n, ambiguous_base.pow(2))
+    } else if let Some(n) = raw.strip_suffix("KB") {
+        (n, ambiguous_base)
+    } else if let Some(n) = raw.strip_suffix('B') {
+        (n, 1)
+    } else {
+        return None;
+    };
+    let n: f64 = num_str.parse().ok()?;
+    Some(Value::Number(round_half_away_from_zero(n * mult as f64).into()))
+}
+
+fn reverse_usd_cents(raw: &str) -> Option<Value> {
+    let n: f64 = raw.strip_prefix('$')?.parse().ok()?;
+    Some(Value::Number(round_half_away_from_zero(n * 100.0).into()))
+}
+
+fn number_or_float(n: f64) -> Value {
+    if n.is_finite() && n == (n as i64) as f64 {
+        Value::Number((n as i64).into())
+    } else {
+        serde_json::Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null)
+    }
+}
+
+/// Round to the nearest integer, ties away from zero — matches
+/// `f64::round()`'s semantics without depending on `std`'s libm bindings,
+/// so the inverse-parser helpers stay available under `no_std` + `alloc`.
+fn round_half_away_from_zero(n: f64) -> i64 {
+    if n >= 0.0 {
+        (n + 0.5) as i64
+    } else {
+        (n - 0.5) as i64
+    }
+}
+
+fn nest_dotted(pairs: Vec<(String, Value)>) -> Value {
+    let mut root = serde_json::Map::new();
+    for (key, value) in pairs {
+        let parts: Vec<&str> = key.split('.').collect();
+        insert_path(&mut root, &parts, value);
+    }
+    Value::Object(root)
+}
+
+fn insert_path(map: &mut serde_json::Map<String, Value>, parts: &[&str], value: Value) {
+    if parts.len() == 1 {
+        map.insert(parts[0].to_string(), value);
+        return;
+    }
+    if let Value::Object(sub) = map
+        .entry(parts[0].to_string())
+        .or_insert_with(|| Value::Object(serde_json::Map::new()))
+    {
+        insert_path(sub, &parts[1..], value);
+    }
+}
+
+/// Parse an `output_yaml` document back into canonical JSON.
+///
+/// Inverts the same grammar `output_yaml` emits: 2-space indentation for
+/// nesting, `- ` / `-` block sequences, `{}`/`[]` for empty containers, and
+/// quoted scalars reconstructing a suffixed key via [`reverse_suffix`] when
+/// the quoted string matches a recognized humanized value.
+pub fn parse_yaml(s: &str) -> Value {
+    parse_yaml_units(s, ByteUnits::default())
+}
+
+/// Like [`parse_yaml`], but resolves ambiguous `_bytes` suffixes (`MB`,
+/// `GB`, ...) against the given `units` base instead of assuming binary.
+pub fn parse_yaml_units(s: &str, units: ByteUnits) -> Value {
+    let lines: Vec<&str> = s.lines().filter(|l| *l != "---").collect();
+    let mut pos = 0;
+    parse_yaml_block(&lines, &mut pos, 0, units)
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn parse_yaml_block(lines: &[&str], pos: &mut usize, indent: usize, units: ByteUnits) -> Value {
+    let mut map = serde_json::Map::new();
+    while *pos < lines.len() {
+        let line = lines[*pos];
+        if line.trim().is_empty() {
+            *pos += 1;
+            continue;
+        }
+        let cur_indent = indent_of(line);
+        if cur_indent != indent {
+            break;
+        }
+        let content = &line[cur_indent..];
+        let colon = match content.find(':') {
+            Some(i) => i,
+            None => {
+                *pos += 1;
+                continue;
+            }
+        };
+        let key = &content[..colon];
+        let rest = content[colon + 1..].trim_start();
+        *pos += 1;
+        if rest == "{}" {
+            map.insert(key.to_string(), Value::Object(serde_json::Map::new()));
+        } else if rest == "[]" {
+            map.insert(key.to_string(), Value::Array(Vec::new()));
+        } else if rest.is_empty() {
+            if *pos < lines.len() && indent_of(lines[*pos]) > indent {
+                let next_indent = indent_of(lines[*pos]);
+                if lines[*pos][next_indent..].starts_with('-') {
+                    map.insert(key.to_string(), parse_yaml_array(lines, pos, next_indent, units));
+                } else {
+                    map.insert(key.to_string(), parse_yaml_block(lines, pos, next_indent, units));
+                }
+            } else {
+                map.insert(key.to_string(), Value::Object(serde_json::Map::new()));
+            }
+        } else {
+            let (full_key, value) = parse_yaml_scalar(key, rest, units);
+            map.insert(full_key, value);
+        }
+    }
+    Value::Object(map)
+}
+
+fn parse_yaml_array(lines: &[&str], pos: &mut usize, indent: usize, units: ByteUnits) -> Value {
+    let mut items = Vec::new();
+    while *pos < lines.len() {
+        let line = lines[*pos];
+        if line.trim().is_empty() {
+            *pos += 1;
+            continue;
+        }
+        let cur_indent = indent_of(line);
+        if cur_indent != indent {
+            break;
+        }
+        let content = &line[cur_indent..];
+        if content == "-" {
+            *pos += 1;
+            let next_indent = if *pos < lines.len() { indent_of(lines[*pos]) } else { indent + 2 };
+            items.push(parse_yaml_block(lines, pos, next_indent, units));
+        } else if let Some(rest) = content.strip_prefix("- ") {
+            *pos += 1;
+            let (_, value) = parse_yaml_scalar("", rest, units);
+            items.push(value);
+        } else {
+            break;
+        }
+    }
+    Value::Array(items)
+}
+
+fn parse_yaml_scalar(key: &str, rest: &str, units: ByteUnits) -> (String, Value) {
+    if let Some(inner) = rest.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        let unescaped = unescape_yaml(inner);
+        if let Some((suffix, value)) = reverse_suffix(&unescaped, units) {
+            return (format!("{key}{suffix}"), value);
+        }
+        return (key.to_string(), Value::String(unescaped));
+    }
+    (key.to_string(), parse_bare_scalar(rest, units))
+}
+
+fn unescape_yaml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
 // ═══════════════════════════════════════════
 // Size parsing
 // ═══════════════════════════════════════════
 
+/// Decimal (SI, base-1000) vs binary (IEC, base-1024) byte unit convention
+/// for [`format_size`]. `parse_size` recognizes both families at once —
+/// `KB` is always decimal and `KiB` is always binary regardless of this
+/// enum — `Base` only matters for rendering, where the convention must be
+/// picked up front.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Base {
+    Decimal,
+    #[default]
+    Binary,
+}
+
 /// Parse a human-readable size string into bytes.
 ///
-/// Accepts `_size` config values: bare number, or number followed by unit letter
-/// (`B`, `K`, `M`, `G`, `T`). Case-insensitive. Trims whitespace.
-/// Returns `None` for invalid or negative input.
+/// Accepts a bare number (raw bytes), a legacy single-letter unit (`K`/`M`/
+/// `G`/`T`, binary like the original `_size` convention), an explicit SI
+/// suffix (`KB`/`MB`/`GB`/`TB`, base-1000), or an explicit IEC suffix
+/// (`KiB`/`MiB`/`GiB`/`TiB`, base-1024) — `KB` and `KiB` are never conflated.
+/// Case-insensitive, tolerates a fractional mantissa and optional whitespace
+/// before the unit. Returns `None` for empty, negative, overflowing, or
+/// otherwise unparseable input.
 ///
 /// ```text
-/// "10M"  → 10_485_760
-/// "1.5K" → 1_536
-/// "512B" → 512
-/// "1024" → 1_024
+/// "10M"    → 10_485_760
+/// "1.5K"   → 1_536
+/// "512B"   → 512
+/// "1024"   → 1_024
+/// "512MB"  → 512_000_000
+/// "1.5GiB" → 1_610_612_736
 /// ```
 pub fn parse_size(s: &str) -> Option<u64> {
     let s = s.trim();
     if s.is_empty() {
         return None;
     }
-    let last = *s.as_bytes().last()?;
-    let (num_str, mult) = match last {
-        b'B' | b'b' => (&s[..s.len() - 1], 1u64),
-        b'K' | b'k' => (&s[..s.len() - 1], 1024),
-        b'M' | b'm' => (&s[..s.len() - 1], 1024 * 1024),
-        b'G' | b'g' => (&s[..s.len() - 1], 1024 * 1024 * 1024),
-        b'T' | b't' => (&s[..s.len() - 1], 1024u64 * 1024 * 1024 * 1024),
-        b'0'..=b'9' | b'.' => (s, 1),
-        _ => return None,
-    };
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+        i += 1;
+    }
+    let num_str = &s[..i];
     if num_str.is_empty() {
         return None;
     }
+    while i < bytes.len() && bytes[i] == b' ' {
+        i += 1;
+    }
+    let mult = size_unit_multiplier(&s[i..].to_ascii_lowercase())?;
     if let Ok(n) = num_str.parse::<u64>() {
         return n.checked_mul(mult);
     }
     let f: f64 = num_str.parse().ok()?;
-    if f < 0.0 || f.is_nan() || f.is_infinite() {
+    if f < 0.0 || !f.is_finite() {
         return None;
     }
     let result = f * mult as f64;
@@ -521,175 +1374,244 @@ pub fn parse_size(s: &str) -> Option<u64> {
     Some(result as u64)
 }
 
+/// Multiplier for a lowercased size-unit suffix, or `None` if `unit` isn't
+/// one of the spellings [`parse_size`] recognizes.
+fn size_unit_multiplier(unit: &str) -> Option<u64> {
+    match unit {
+        "" | "b" => Some(1),
+        "k" | "kib" => Some(1024),
+        "kb" => Some(1_000),
+        "m" | "mib" => Some(1024 * 1024),
+        "mb" => Some(1_000_000),
+        "g" | "gib" => Some(1024 * 1024 * 1024),
+        "gb" => Some(1_000_000_000),
+        "t" | "tib" => Some(1024u64 * 1024 * 1024 * 1024),
+        "tb" => Some(1_000_000_000_000),
+        _ => None,
+    }
+}
+
+/// Render a byte count as the largest unit ≤ the value, with up to one
+/// decimal place, per `base` — the inverse of [`parse_size`].
+///
+/// ```text
+/// format_size(10_485_760, Base::Binary)  → "10.0MiB"
+/// format_size(512_000_000, Base::Decimal) → "512.0MB"
+/// format_size(512, Base::Binary)          → "512B"
+/// ```
+pub fn format_size(bytes: u64, base: Base) -> String {
+    let (unit, suffixes) = match base {
+        Base::Decimal => (1000u64, ["KB", "MB", "GB", "TB"]),
+        Base::Binary => (1024u64, ["KiB", "MiB", "GiB", "TiB"]),
+    };
+    let kb = unit as f64;
+    let mb = kb * unit as f64;
+    let gb = mb * unit as f64;
+    let tb = gb * unit as f64;
+
+    let b = bytes as f64;
+    if b >= tb {
+        format!("{:.1}{}", b / tb, suffixes[3])
+    } else if b >= gb {
+        format!("{:.1}{}", b / gb, suffixes[2])
+    } else if b >= mb {
+        format!("{:.1}{}", b / mb, suffixes[1])
+    } else if b >= kb {
+        format!("{:.1}{}", b / kb, suffixes[0])
+    } else {
+        format!("{bytes}B")
+    }
+}
+
 // ═══════════════════════════════════════════
-// Tests
+// Duration parsing
 // ═══════════════════════════════════════════
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::Value;
-
-    const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../spec/fixtures");
-
-    fn load_fixture(name: &str) -> Value {
-        let path = format!("{}/{}", FIXTURES_DIR, name);
-        let data = std::fs::read_to_string(&path)
-            .unwrap_or_else(|e| panic!("failed to read {}: {}", path, e));
-        serde_json::from_str(&data)
-            .unwrap_or_else(|e| panic!("failed to parse {}: {}", path, e))
-    }
-
-    #[test]
-    fn test_plain_fixtures() {
-        let cases = load_fixture("plain.json");
-        for case in cases.as_array().expect("plain.json must be an array") {
-            let name = case["name"].as_str().expect("missing name");
-            let input = &case["input"];
-            let plain = to_plain(input);
-            for expected in case["contains"].as_array().expect("missing contains") {
-                let s = expected.as_str().expect("contains must be strings");
-                assert!(plain.contains(s), "[plain/{name}] expected {s:?} in {plain:?}");
-            }
-            if let Some(not_contains) = case.get("not_contains") {
-                for nc in not_contains.as_array().expect("not_contains must be array") {
-                    let s = nc.as_str().expect("not_contains must be strings");
-                    assert!(!plain.contains(s), "[plain/{name}] unexpected {s:?} in {plain:?}");
-                }
+/// Parse a human-readable duration string into milliseconds.
+///
+/// Accepts the same unit spellings the formatters emit (`ns`, `us`/`μs`,
+/// `ms`, `s`, `m`/`minutes`, `h`/`hours`, `d`/`days`), tolerates decimals
+/// (`"1.5s"`), allows a space between a number and its unit (`"30 minutes"`),
+/// and sums compound forms (`"1h30m"`, `"1d12h"`). Returns `None` for empty,
+/// negative, or otherwise unparseable input, just like [`parse_size`].
+///
+/// ```text
+/// "1.28s"      → 1280
+/// "30 minutes" → 1_800_000
+/// "24h"        → 86_400_000
+/// "1d12h"      → 129_600_000
+/// ```
+pub fn parse_duration(s: &str) -> Option<u64> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    let mut total_ms = 0.0f64;
+    let mut matched_any = false;
+    while i < bytes.len() {
+        while i < bytes.len() && bytes[i] == b' ' {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            break;
+        }
+        let num_start = i;
+        if !(bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+            return None;
+        }
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        if i < bytes.len() && bytes[i] == b'.' {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
             }
         }
+        let n: f64 = s[num_start..i].parse().ok()?;
+        if n.is_nan() || n.is_infinite() || n < 0.0 {
+            return None;
+        }
+        while i < bytes.len() && bytes[i] == b' ' {
+            i += 1;
+        }
+        let unit_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_digit() && bytes[i] != b'.' && bytes[i] != b' ' {
+            i += 1;
+        }
+        let mult_ms: f64 = match &s[unit_start..i] {
+            "ns" => 1e-6,
+            "us" | "μs" => 1e-3,
+            "ms" => 1.0,
+            "s" => 1_000.0,
+            "m" | "minutes" => 60_000.0,
+            "h" | "hours" => 3_600_000.0,
+            "d" | "days" => 86_400_000.0,
+            _ => return None,
+        };
+        total_ms += n * mult_ms;
+        matched_any = true;
     }
+    if !matched_any || !total_ms.is_finite() || total_ms > u64::MAX as f64 {
+        return None;
+    }
+    Some(round_half_away_from_zero(total_ms) as u64)
+}
 
-    #[test]
-    fn test_yaml_fixtures() {
-        let cases = load_fixture("yaml.json");
-        for case in cases.as_array().expect("yaml.json must be an array") {
-            let name = case["name"].as_str().expect("missing name");
-            let input = &case["input"];
-            let yaml = to_yaml(input);
-            if let Some(prefix) = case.get("starts_with") {
-                let s = prefix.as_str().expect("starts_with must be string");
-                assert!(yaml.starts_with(s), "[yaml/{name}] expected starts_with {s:?} in {yaml:?}");
-            }
-            if let Some(contains) = case.get("contains") {
-                for expected in contains.as_array().expect("contains must be array") {
-                    let s = expected.as_str().expect("contains must be strings");
-                    assert!(yaml.contains(s), "[yaml/{name}] expected {s:?} in {yaml:?}");
-                }
-            }
-        }
+// ═══════════════════════════════════════════
+// Money parsing
+// ═══════════════════════════════════════════
+
+/// A parsed monetary amount: an integer count of minor units (cents) plus
+/// its resolved ISO 4217 code.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Money {
+    pub minor_units: i64,
+    pub code: &'static str,
+}
+
+/// Parse a human-written money string into a [`Money`], resolving a leading
+/// currency symbol (`$`, `€`, `£`, `¥`) or a standalone three-letter ISO
+/// code (`"USD 1299"`, `"1299 USD"`) to its code, and handling both the US
+/// grouping convention (`1,299.00`) and the EU convention (`1.299,00`).
+///
+/// When both `,` and `.` appear, whichever comes last is the decimal point
+/// and the other is a thousands separator; with only one present, it's a
+/// thousands separator if exactly three digits follow it, otherwise the
+/// decimal point. `¥` always resolves to `JPY`, never `CNY`. A `-` anywhere
+/// in the string (`"-$5"`, `"$-5"`) produces a negative `minor_units`.
+/// Returns `None` when no currency marker is found or the numeric part
+/// doesn't parse.
+///
+/// ```text
+/// "$1,299.00" → Money { minor_units: 129_900, code: "USD" }
+/// "€ 19,99"   → Money { minor_units: 1_999, code: "EUR" }
+/// "£50"       → Money { minor_units: 5_000, code: "GBP" }
+/// "USD 1299"  → Money { minor_units: 129_900, code: "USD" }
+/// "-$5"       → Money { minor_units: -500, code: "USD" }
+/// ```
+pub fn extract_money(input: &str) -> Option<Money> {
+    let s = input.trim();
+    if s.is_empty() {
+        return None;
+    }
+    let code = if let Some(symbol) = s.chars().find(|c| currency_symbol_code(*c).is_some()) {
+        currency_symbol_code(symbol)?
+    } else {
+        s.split_whitespace()
+            .find_map(|token| (token.len() == 3 && token.is_ascii()).then(|| iso_currency_code(token)).flatten())?
+    };
+    let negative = s.contains('-');
+    let numeric: String = s.chars().filter(|c| c.is_ascii_digit() || *c == ',' || *c == '.').collect();
+    let minor_units = parse_money_amount(&numeric)?;
+    let minor_units = if negative { -minor_units } else { minor_units };
+    Some(Money { minor_units, code })
+}
+
+/// Resolve a money string to just its ISO 4217 code, discarding the amount.
+/// A thin wrapper over [`extract_money`].
+pub fn extract_currency_code(input: &str) -> Option<&'static str> {
+    extract_money(input).map(|m| m.code)
+}
+
+/// Map a currency symbol to its ISO 4217 code.
+fn currency_symbol_code(symbol: char) -> Option<&'static str> {
+    match symbol {
+        '$' => Some("USD"),
+        '€' => Some("EUR"),
+        '£' => Some("GBP"),
+        '¥' => Some("JPY"),
+        _ => None,
     }
+}
 
-    #[test]
-    fn test_redact_fixtures() {
-        let cases = load_fixture("redact.json");
-        for case in cases.as_array().expect("redact.json must be an array") {
-            let name = case["name"].as_str().expect("missing name");
-            let mut input = case["input"].clone();
-            let expected = &case["expected"];
-            redact_secrets(&mut input);
-            assert_eq!(&input, expected, "[redact/{name}]");
-        }
-    }
-
-    #[test]
-    fn test_protocol_fixtures() {
-        let cases = load_fixture("protocol.json");
-        for case in cases.as_array().expect("protocol.json must be an array") {
-            let name = case["name"].as_str().expect("missing name");
-            let typ = case["type"].as_str().expect("missing type");
-            let args = &case["args"];
-            let result = match typ {
-                "ok" => ok(args["result"].clone()),
-                "ok_trace" => ok_trace(args["result"].clone(), args["trace"].clone()),
-                "error" => error(args["message"].as_str().expect("missing message")),
-                "error_trace" => error_trace(
-                    args["message"].as_str().expect("missing message"),
-                    args["trace"].clone(),
-                ),
-                "startup" => startup(
-                    args["config"].clone(),
-                    args["args"].clone(),
-                    args["env"].clone(),
-                ),
-                "status" => {
-                    let code = args["code"].as_str().expect("missing code");
-                    let fields = args["fields"].clone();
-                    status(code, fields)
-                }
-                other => panic!("unknown protocol type: {other}"),
-            };
-            if let Some(expected) = case.get("expected") {
-                assert_eq!(&result, expected, "[protocol/{name}]");
-            }
-            if let Some(expected_contains) = case.get("expected_contains") {
-                let ec = expected_contains.as_object().expect("expected_contains must be object");
-                let ro = result.as_object().expect("result must be object");
-                for (k, v) in ec {
-                    assert_eq!(ro.get(k).unwrap_or(&Value::Null), v, "[protocol/{name}] key {k}");
-                }
-            }
-        }
+/// Resolve a three-letter token (any case) to a known ISO 4217 code.
+fn iso_currency_code(token: &str) -> Option<&'static str> {
+    match token.to_ascii_uppercase().as_str() {
+        "USD" => Some("USD"),
+        "EUR" => Some("EUR"),
+        "GBP" => Some("GBP"),
+        "JPY" => Some("JPY"),
+        "CAD" => Some("CAD"),
+        "AUD" => Some("AUD"),
+        "CHF" => Some("CHF"),
+        "CNY" => Some("CNY"),
+        "INR" => Some("INR"),
+        _ => None,
     }
+}
 
-    #[test]
-    fn test_exact_fixtures() {
-        let cases = load_fixture("exact.json");
-        for case in cases.as_array().expect("exact.json must be an array") {
-            let name = case["name"].as_str().expect("missing name");
-            let format = case["format"].as_str().expect("missing format");
-            let input = &case["input"];
-            let expected = case["expected"].as_str().expect("missing expected");
-            let got = match format {
-                "plain" => to_plain(input),
-                "yaml" => to_yaml(input),
-                other => panic!("unknown format: {other}"),
-            };
-            assert_eq!(got, expected, "[exact/{name}]");
-        }
-    }
-
-    #[test]
-    fn test_helper_fixtures() {
-        let cases = load_fixture("helpers.json");
-        for case in cases.as_array().expect("helpers.json must be an array") {
-            let name = case["name"].as_str().expect("missing name");
-            let test_cases = case["cases"].as_array().expect("missing cases");
-            match name {
-                "format_bytes_human" => {
-                    for tc in test_cases {
-                        let arr = tc.as_array().expect("case must be [input, expected]");
-                        let input = arr[0].as_i64().expect("input must be i64");
-                        let expected = arr[1].as_str().expect("expected must be string");
-                        assert_eq!(format_bytes_human(input), expected, "[helpers/format_bytes_human({input})]");
-                    }
-                }
-                "format_with_commas" => {
-                    for tc in test_cases {
-                        let arr = tc.as_array().expect("case must be [input, expected]");
-                        let input = arr[0].as_u64().expect("input must be u64");
-                        let expected = arr[1].as_str().expect("expected must be string");
-                        assert_eq!(format_with_commas(input), expected, "[helpers/format_with_commas({input})]");
-                    }
-                }
-                "extract_currency_code" => {
-                    for tc in test_cases {
-                        let arr = tc.as_array().expect("case must be [input, expected]");
-                        let input = arr[0].as_str().expect("input must be string");
-                        let expected = if arr[1].is_null() { None } else { arr[1].as_str() };
-                        assert_eq!(extract_currency_code(input), expected, "[helpers/extract_currency_code({input})]");
-                    }
-                }
-                "parse_size" => {
-                    for tc in test_cases {
-                        let arr = tc.as_array().expect("case must be [input, expected]");
-                        let input = arr[0].as_str().expect("input must be string");
-                        let expected = if arr[1].is_null() { None } else { arr[1].as_u64() };
-                        assert_eq!(parse_size(input), expected, "[helpers/parse_size({input:?})]");
-                    }
-                }
-                other => panic!("unknown helper: {other}"),
-            }
+/// Parse a cleaned numeric span (digits, `,`, `.` only) into minor units,
+/// resolving which punctuation char is the decimal point per the US/EU
+/// grouping heuristic described on [`extract_money`].
+fn parse_money_amount(numeric: &str) -> Option<i64> {
+    if numeric.is_empty() {
+        return None;
+    }
+    let last_comma = numeric.rfind(',');
+    let last_dot = numeric.rfind('.');
+    let decimal_sep = match (last_comma, last_dot) {
+        (Some(c), Some(d)) => Some(if d > c { '.' } else { ',' }),
+        (Some(c), None) if numeric.len() - c - 1 == 3 => None,
+        (Some(_), None) => Some(','),
+        (None, Some(d)) if numeric.len() - d - 1 == 3 => None,
+        (None, Some(_)) => Some('.'),
+        (None, None) => None,
+    };
+    let mut cleaned = String::with_capacity(numeric.len());
+    for c in numeric.chars() {
+        match c {
+            '0'..='9' => cleaned.push(c),
+            ',' | '.' if Some(c) == decimal_sep => cleaned.push('.'),
+            ',' | '.' => {}
+            _ => return None,
         }
     }
+    let value: f64 = cleaned.parse().ok()?;
+    if !value.is_finite() || value < 0.0 {
+        return None;
+    }
+    Some(round_half_away_from_zero(value * 100.0))
 }
@@ -0,0 +1,63 @@
+//! Generative property/fuzz coverage for the parsing helpers.
+//!
+//! The fixture-driven [`tests`][crate::tests] suite only exercises hand-picked
+//! inputs; these properties cover invariants that should hold for *any*
+//! input, which a finite example table can't guarantee. Feature-gated behind
+//! `proptest` (`cargo test --features proptest`) — expensive relative to the
+//! JSON-table tests, which remain the primary, always-on gate.
+
+use proptest::prelude::*;
+
+use super::*;
+
+proptest! {
+    /// `parse_size` must never panic, on any string at all — not just the
+    /// well-formed ones the example table covers.
+    #[test]
+    fn parse_size_never_panics(s in ".{0,64}") {
+        let _ = parse_size(&s);
+    }
+
+    /// Whatever `parse_size` accepts, `format_size` must be able to render
+    /// back to a string that reparses to an equivalent size — equivalent
+    /// meaning within the rounding `format_size`'s one decimal place of
+    /// precision can introduce.
+    #[test]
+    fn parse_size_round_trips_through_format_size(bytes in 0u64..=(1u64 << 50)) {
+        let rendered = format_size(bytes, Base::Binary);
+        let reparsed = parse_size(&rendered).expect("format_size output must always reparse");
+        let diff = reparsed.abs_diff(bytes) as f64;
+        let tolerance = (bytes as f64) * 0.06 + 1.0;
+        prop_assert!(
+            diff <= tolerance,
+            "format_size({bytes}) = {rendered:?}, which reparsed to {reparsed} (tolerance {tolerance})"
+        );
+    }
+
+    /// Whatever `extract_currency_code` returns must be a well-formed ISO
+    /// 4217 code shape — exactly three uppercase ASCII letters — or `None`.
+    /// Never a lowercase code, a partial match, or anything else.
+    #[test]
+    fn extract_currency_code_is_three_uppercase_letters_or_none(s in ".{0,64}") {
+        if let Some(code) = extract_currency_code(&s) {
+            prop_assert_eq!(code.len(), 3);
+            prop_assert!(code.bytes().all(|b| b.is_ascii_uppercase()));
+        }
+    }
+
+    /// A generated `"<number><unit>"` string always parses to the
+    /// arithmetically correct byte count for that unit.
+    #[test]
+    fn number_unit_strings_parse_to_arithmetic_byte_count(
+        n in 0u64..1_000_000u64,
+        unit in prop_oneof![
+            Just("b"), Just("k"), Just("m"), Just("g"), Just("t"),
+            Just("kb"), Just("mb"), Just("gb"), Just("tb"),
+            Just("kib"), Just("mib"), Just("gib"), Just("tib"),
+        ],
+    ) {
+        let s = format!("{n}{unit}");
+        let expected = size_unit_multiplier(unit).and_then(|mult| n.checked_mul(mult));
+        prop_assert_eq!(parse_size(&s), expected);
+    }
+}
@@ -0,0 +1,119 @@
+//! Programmatic suffix registry, for custom units a [`rules::RuleSet`][crate::rules::RuleSet]
+//! YAML document can't express.
+//!
+//! `RuleSet` covers currency/duration/byte/percent/passthrough strategies
+//! declaratively, which is enough for most house conventions, but some
+//! units need real code — scaling wei down to ETH, converting a raw
+//! Celsius tenth to a display string with a sign. A [`SuffixRegistry`] maps
+//! a suffix to an arbitrary formatter closure instead, checked after
+//! `RuleSet` and ahead of the built-in suffix table, so a registered suffix
+//! can add a new unit or override a built-in one.
+//!
+//! ```
+//! use agent_first_data::registry::SuffixRegistry;
+//! use agent_first_data::output_plain_registry;
+//! use serde_json::json;
+//!
+//! let registry = SuffixRegistry::new().register("_wei", |v| {
+//!     let wei = v.as_f64().unwrap_or(0.0);
+//!     format!("{:.4} ETH", wei / 1e18)
+//! });
+//! let out = output_plain_registry(&json!({"balance_wei": 2_500_000_000_000_000_000i64}), &registry);
+//! assert_eq!(out, "balance=2.5000 ETH");
+//! ```
+//!
+//! `output_json` never consults this registry (or `RuleSet`'s format
+//! strategies): JSON is the lossless format and only honors `_secret`
+//! redaction, which is a `RuleSet` concern, not a formatting one.
+
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, format, string::{String, ToString}, vec::Vec};
+
+use serde_json::Value;
+
+use crate::CowStr;
+
+type Formatter = Box<dyn Fn(&Value) -> String>;
+
+/// A builder-style, open-ended map from key suffix to formatter closure.
+///
+/// [`SuffixRegistry::new`] pre-registers the built-in suffixes that don't
+/// need external configuration to format (no [`crate::TimeMode`] or
+/// [`crate::ByteUnits`] choice) — [`SuffixRegistry::default`] starts empty.
+/// Registering a suffix that's already present (built-in or user-added)
+/// replaces it; lookup always prefers the longest matching suffix, so
+/// registering both `_ms` and `_epoch_ms` resolves each key correctly
+/// regardless of registration order.
+#[derive(Default)]
+pub struct SuffixRegistry {
+    entries: Vec<(String, Formatter)>,
+}
+
+impl SuffixRegistry {
+    /// An empty registry — equivalent to not passing one at all.
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// A registry pre-populated with the subset of the built-in suffix
+    /// table that formats from the value alone: `_secret`, `_bytes` (binary
+    /// base), `_ms`/`_ns`/`_us`/`_s`, `_usd_cents`/`_eur_cents`/`_jpy`,
+    /// `_msats`/`_sats`/`_btc`, `_percent`, `_minutes`/`_hours`/`_days`.
+    ///
+    /// Suffixes whose rendering depends on [`crate::TimeMode`]
+    /// (`_epoch_ms`/`_epoch_s`/`_epoch_ns`/`_rfc3339`) and the
+    /// currency-code-from-key `_{code}_cents` suffix aren't included here —
+    /// a registry closure only sees the value, not the mode or the key —
+    /// use [`crate::output_plain_full`]/[`crate::output_yaml_full`] for those.
+    pub fn new() -> Self {
+        Self::default()
+            .register("_secret", |_| "***".to_string())
+            .register("_bytes", |v| {
+                crate::format_bytes_human(v.as_i64().unwrap_or(0), crate::ByteUnits::default())
+            })
+            .register("_ms", |v| crate::format_duration_ms(v).unwrap_or_default())
+            .register("_ns", |v| format!("{}ns", crate::plain_scalar(v)))
+            .register("_us", |v| format!("{}\u{3bc}s", crate::plain_scalar(v)))
+            .register("_s", |v| format!("{}s", crate::plain_scalar(v)))
+            .register("_usd_cents", |v| {
+                let n = v.as_u64().unwrap_or(0);
+                format!("${}.{:02}", n / 100, n % 100)
+            })
+            .register("_eur_cents", |v| {
+                let n = v.as_u64().unwrap_or(0);
+                format!("\u{20ac}{}.{:02}", n / 100, n % 100)
+            })
+            .register("_jpy", |v| {
+                format!("\u{a5}{}", crate::format_with_commas(v.as_u64().unwrap_or(0)))
+            })
+            .register("_msats", |v| format!("{}msats", crate::plain_scalar(v)))
+            .register("_sats", |v| format!("{}sats", crate::plain_scalar(v)))
+            .register("_btc", |v| format!("{} BTC", crate::plain_scalar(v)))
+            .register("_percent", |v| format!("{}%", crate::plain_scalar(v)))
+            .register("_minutes", |v| format!("{} minutes", crate::plain_scalar(v)))
+            .register("_hours", |v| format!("{} hours", crate::plain_scalar(v)))
+            .register("_days", |v| format!("{} days", crate::plain_scalar(v)))
+    }
+
+    /// Register `suffix` with `formatter`, replacing any existing entry for
+    /// the same suffix. Consumes and returns `self` for chaining.
+    pub fn register(mut self, suffix: impl Into<String>, formatter: impl Fn(&Value) -> String + 'static) -> Self {
+        let suffix = suffix.into();
+        self.entries.retain(|(s, _)| *s != suffix);
+        self.entries.push((suffix, Box::new(formatter)));
+        self
+    }
+
+    /// Look up the longest suffix of `key` with a registered formatter and
+    /// apply it, honoring the same all-lowercase/all-uppercase-only suffix
+    /// rule as the built-in table. `None` if nothing registered matches.
+    pub(crate) fn lookup<'a>(&self, key: &'a str, value: &Value) -> Option<(CowStr<'a>, String)> {
+        self.entries
+            .iter()
+            .filter_map(|(suffix, formatter)| {
+                crate::match_suffix(key, suffix).map(|rest| (suffix.len(), rest, formatter))
+            })
+            .max_by_key(|(len, _, _)| *len)
+            .map(|(_, rest, formatter)| (CowStr::Borrowed(rest), formatter(value)))
+    }
+}
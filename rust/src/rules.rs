@@ -0,0 +1,154 @@
+//! User-defined formatting rules, loaded from a YAML document.
+//!
+//! The suffix table built into the crate (`_ms`, `_bytes`, `_usd_cents`, ...)
+//! covers the common cases, but a user may need a unit or currency of their
+//! own — `_kwh`, `_rpm`, a house-token currency — without patching the crate.
+//! A [`RuleSet`] describes those suffixes declaratively and is checked ahead
+//! of the built-in table by every output function's `_rules` variant
+//! ([`crate::output_yaml_rules`], [`crate::output_plain_rules`],
+//! [`crate::output_json_rules`]), so a user rule can add a new suffix or
+//! override a built-in one.
+//!
+//! ```yaml
+//! rules:
+//!   - suffix: _kwh
+//!     format: { kind: passthrough, unit: kWh }
+//!   - suffix: _house_credits
+//!     format: { kind: currency, symbol: "H$", decimals: 2 }
+//!   - suffix: _session_token
+//!     redact: true
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::CowStr;
+
+/// How a custom suffix's value should be rendered once its suffix is
+/// stripped. Mirrors the subset of the built-in suffix table that's
+/// expressible declaratively; `_epoch_ms`/`_rfc3339`-style time fields stay
+/// built-in only, since they depend on [`crate::TimeMode`].
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum FormatStrategy {
+    /// Value is milliseconds; rendered the same way the built-in `_ms`
+    /// suffix is (`"42ms"` under 1000, `"1.28s"` at or above it).
+    Duration,
+    /// Value is a byte count; rendered the same way the built-in `_bytes`
+    /// suffix is (`"5.0MB"`).
+    Bytes,
+    /// Value is an integer in minor units (e.g. cents); rendered as
+    /// `{symbol}{major}.{minor}` with `decimals` digits after the point.
+    Currency { symbol: String, decimals: u32 },
+    /// Value is a number; rendered as `{value}%`.
+    Percent,
+    /// Value is a number; rendered as `{value} {unit}`.
+    Passthrough { unit: String },
+}
+
+/// One user-defined suffix rule.
+///
+/// `redact: true` takes priority over `format` (redaction implies the value
+/// is secret, not merely reformatted) and needs no format strategy at all.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SuffixRule {
+    pub suffix: String,
+    #[serde(default)]
+    pub redact: bool,
+    #[serde(default)]
+    pub format: Option<FormatStrategy>,
+}
+
+/// A set of user-defined suffix rules, layered over the crate's built-in
+/// suffix table. An empty (default) `RuleSet` leaves built-in behavior
+/// unchanged.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RuleSet {
+    #[serde(default)]
+    pub rules: Vec<SuffixRule>,
+}
+
+impl RuleSet {
+    /// Parse a YAML rules document (see module docs for the schema).
+    ///
+    /// Requires the (default-on) `std` feature — YAML parsing isn't
+    /// available in a `no_std` build, though the `RuleSet` type and the
+    /// rest of the formatting core are.
+    #[cfg(feature = "std")]
+    pub fn load(yaml: &str) -> Result<RuleSet, String> {
+        serde_yaml::from_str(yaml).map_err(|e| e.to_string())
+    }
+
+    /// Layer `self`'s rules over `defaults`, `self` winning on a shared
+    /// suffix. Use this to merge a user-loaded `RuleSet` over the crate's
+    /// built-in defaults when a project wants to keep both.
+    pub fn merged_over(self, defaults: RuleSet) -> RuleSet {
+        let mut rules = self.rules;
+        for default_rule in defaults.rules {
+            if !rules.iter().any(|r| r.suffix == default_rule.suffix) {
+                rules.push(default_rule);
+            }
+        }
+        RuleSet { rules }
+    }
+
+    /// True if `key`'s suffix matches a rule with `redact: true`.
+    pub(crate) fn is_redacted(&self, key: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|r| r.redact && crate::match_suffix(key, &r.suffix).is_some())
+    }
+
+    /// Look up and apply the rule matching `key`'s suffix, honoring the same
+    /// all-lowercase/all-uppercase-only suffix-case rule as the built-in
+    /// table. Returns `(stripped key, formatted value)`, or `None` if no
+    /// rule matches or the rule's strategy doesn't fit `value`'s type.
+    pub(crate) fn strip_and_format<'a>(
+        &self,
+        key: &'a str,
+        value: &Value,
+        units: crate::ByteUnits,
+    ) -> Option<(CowStr<'a>, String)> {
+        for rule in &self.rules {
+            let Some(rest) = crate::match_suffix(key, &rule.suffix) else { continue };
+            if rule.redact {
+                return Some((CowStr::Borrowed(rest), "***".to_string()));
+            }
+            let formatted = match rule.format.as_ref()? {
+                FormatStrategy::Duration => crate::format_duration_ms(value)?,
+                FormatStrategy::Bytes => crate::format_bytes_human(value.as_i64()?, units),
+                FormatStrategy::Currency { symbol, decimals } => {
+                    format_currency(value.as_u64()?, symbol, *decimals)
+                }
+                FormatStrategy::Percent => {
+                    if !value.is_number() {
+                        return None;
+                    }
+                    format!("{}%", crate::plain_scalar(value))
+                }
+                FormatStrategy::Passthrough { unit } => {
+                    if !value.is_number() {
+                        return None;
+                    }
+                    format!("{} {unit}", crate::plain_scalar(value))
+                }
+            };
+            return Some((CowStr::Borrowed(rest), formatted));
+        }
+        None
+    }
+}
+
+fn format_currency(n: u64, symbol: &str, decimals: u32) -> String {
+    let divisor = 10u64.pow(decimals);
+    let major = n / divisor;
+    let minor = n % divisor;
+    format!("{symbol}{major}.{minor:0width$}", width = decimals as usize)
+}
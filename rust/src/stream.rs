@@ -0,0 +1,56 @@
+//! Streaming NDJSON emitter for multi-frame agent tool output.
+//!
+//! The protocol builders ([`crate::build_json_ok`], [`crate::build_json_error`],
+//! [`crate::build_json`] with codes like `"progress"`) each produce a single
+//! value. A long-running tool instead needs to emit an ordered stream of
+//! those values — zero or more non-terminal status/progress frames followed
+//! by exactly one terminal `ok`/`error` frame. [`FrameWriter`] wraps any
+//! `io::Write` and enforces that invariant, writing each frame as one
+//! redacted JSON line (the same redaction [`crate::output_json`] applies)
+//! and flushing after every write so callers can stream incrementally.
+
+use std::io::{self, Write};
+
+use serde_json::Value;
+
+/// Writes a sequence of AFD protocol frames as newline-delimited JSON.
+///
+/// Enforces "zero or more non-terminal frames, then exactly one terminal
+/// `ok`/`error` frame": once a frame with `code: "ok"` or `code: "error"`
+/// has been written, any further [`write_frame`](FrameWriter::write_frame)
+/// call returns an error instead of writing.
+pub struct FrameWriter<W: Write> {
+    writer: W,
+    terminated: bool,
+}
+
+impl<W: Write> FrameWriter<W> {
+    /// Wrap `writer`. No frames have been written yet.
+    pub fn new(writer: W) -> Self {
+        FrameWriter { writer, terminated: false }
+    }
+
+    /// Write `frame` as one redacted JSON line and flush.
+    ///
+    /// Redaction matches [`crate::output_json`]: `_secret`/`_SECRET` fields
+    /// are replaced with `"***"`. Returns an error without writing if a
+    /// terminal frame has already been written.
+    pub fn write_frame(&mut self, frame: &Value) -> io::Result<()> {
+        if self.terminated {
+            return Err(io::Error::other(
+                "FrameWriter: cannot write a frame after a terminal ok|error frame",
+            ));
+        }
+        writeln!(self.writer, "{}", crate::output_json(frame))?;
+        self.writer.flush()?;
+        if matches!(frame["code"].as_str(), Some("ok") | Some("error")) {
+            self.terminated = true;
+        }
+        Ok(())
+    }
+
+    /// True once a terminal (`ok`/`error`) frame has been written.
+    pub fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
@@ -68,47 +68,228 @@ fn test_protocol_fixtures() {
     }
 }
 
+/// Build the `[input, ..., expected]` ignore-list key and pass/fail outcome
+/// for one case, given the already-decoded `actual`/`expected` values.
+/// Shared by every entry in [`helper_registry`] so each one only has to
+/// describe how to decode its own case shape.
+macro_rules! check {
+    ($name:expr, $input:expr, $actual:expr, $expected:expr) => {{
+        let key = format!("{}/{}", $name, $input);
+        let (actual, expected) = ($actual, $expected);
+        if actual == expected {
+            (key, Ok(()))
+        } else {
+            (key, Err(format!("expected {expected:?}, got {actual:?}")))
+        }
+    }};
+}
+
+/// One registered helper's case-checker: given its own name (for building the
+/// ignore-list key) and a `[input, ..., expected]` fixture case, decode it,
+/// run the helper, and report whether it matched.
+type HelperCheck = fn(&str, &Value) -> (String, Result<(), String>);
+
+fn check_format_bytes_human(name: &str, tc: &Value) -> (String, Result<(), String>) {
+    let arr = tc.as_array().expect("case must be an array");
+    let input = arr[0].as_i64().expect("input must be i64");
+    let expected = arr[1].as_str().expect("expected must be string");
+    check!(name, input, format_bytes_human(input, ByteUnits::default()), expected.to_string())
+}
+
+fn check_format_with_commas(name: &str, tc: &Value) -> (String, Result<(), String>) {
+    let arr = tc.as_array().expect("case must be an array");
+    let input = arr[0].as_u64().expect("input must be u64");
+    let expected = arr[1].as_str().expect("expected must be string");
+    check!(name, input, format_with_commas(input), expected.to_string())
+}
+
+fn check_extract_currency_code(name: &str, tc: &Value) -> (String, Result<(), String>) {
+    let arr = tc.as_array().expect("case must be an array");
+    let input = arr[0].as_str().expect("input must be string");
+    let expected = if arr[1].is_null() { None } else { arr[1].as_str().map(str::to_string) };
+    check!(name, input, extract_currency_code(input).map(str::to_string), expected)
+}
+
+fn check_extract_money(name: &str, tc: &Value) -> (String, Result<(), String>) {
+    let arr = tc.as_array().expect("case must be an array");
+    let input = arr[0].as_str().expect("input must be string");
+    let expected = if arr[1].is_null() {
+        None
+    } else {
+        let code = arr[1].as_str().expect("expected_code must be string").to_string();
+        let minor_units = arr[2].as_i64().expect("expected_minor_units must be i64");
+        Some((code, minor_units))
+    };
+    let actual = extract_money(input).map(|m| (m.code.to_string(), m.minor_units));
+    check!(name, input, actual, expected)
+}
+
+fn check_parse_size(name: &str, tc: &Value) -> (String, Result<(), String>) {
+    let arr = tc.as_array().expect("case must be an array");
+    let input = arr[0].as_str().expect("input must be string");
+    let expected = if arr[1].is_null() { None } else { arr[1].as_u64() };
+    check!(name, input, parse_size(input), expected)
+}
+
+fn check_format_size(name: &str, tc: &Value) -> (String, Result<(), String>) {
+    let arr = tc.as_array().expect("case must be an array");
+    let bytes = arr[0].as_u64().expect("bytes must be u64");
+    let base_str = arr[1].as_str().expect("base must be string");
+    let base = match base_str {
+        "decimal" => Base::Decimal,
+        "binary" => Base::Binary,
+        other => return (format!("{name}/{bytes}:{other}"), Err(format!("unknown base: {other}"))),
+    };
+    let expected = arr[2].as_str().expect("expected must be string");
+    check!(name, format!("{bytes}:{base_str}"), format_size(bytes, base), expected.to_string())
+}
+
+fn check_parse_duration(name: &str, tc: &Value) -> (String, Result<(), String>) {
+    let arr = tc.as_array().expect("case must be an array");
+    let input = arr[0].as_str().expect("input must be string");
+    let expected = if arr[1].is_null() { None } else { arr[1].as_u64() };
+    check!(name, input, parse_duration(input), expected)
+}
+
+/// All string helpers covered by `helpers.json`, keyed by name. Adding a new
+/// helper to the conformance suite means writing one `check_*` function and
+/// registering it here — the dispatcher never needs to change.
+fn helper_registry() -> BTreeMap<&'static str, HelperCheck> {
+    let mut registry: BTreeMap<&'static str, HelperCheck> = BTreeMap::new();
+    registry.insert("format_bytes_human", check_format_bytes_human);
+    registry.insert("format_with_commas", check_format_with_commas);
+    registry.insert("extract_currency_code", check_extract_currency_code);
+    registry.insert("extract_money", check_extract_money);
+    registry.insert("parse_size", check_parse_size);
+    registry.insert("format_size", check_format_size);
+    registry.insert("parse_duration", check_parse_duration);
+    registry
+}
+
+/// Evaluate one `[input, ..., expected]` case for a named helper by looking
+/// it up in `registry`.
+///
+/// Returns the `helper/input` ignore-list key alongside `Ok(())` on a match
+/// or `Err(message)` describing the mismatch — never panics, so the caller
+/// can run every case in a fixture file even after some have failed. An
+/// unregistered helper name is reported the same way, listing what *is*
+/// registered instead of panicking.
+fn eval_helper_case(
+    registry: &BTreeMap<&'static str, HelperCheck>,
+    name: &str,
+    tc: &Value,
+) -> (String, Result<(), String>) {
+    match registry.get(name) {
+        Some(check) => check(name, tc),
+        None => {
+            let known: Vec<&str> = registry.keys().copied().collect();
+            (format!("{name}/?"), Err(format!("unknown helper: {name} (registered: {})", known.join(", "))))
+        }
+    }
+}
+
+/// Scope guard that names the in-flight fixture case if a panic unwinds
+/// through it — e.g. a `check_*` function's `.expect()` on a malformed case,
+/// or a helper itself panicking on a path `eval_helper_case` can't catch.
+/// `eval_helper_case`'s `Err` return already covers ordinary mismatches;
+/// this covers the panic path so a failure is traceable to one case out of
+/// hundreds instead of just an `.expect()` message with no input in sight.
+struct CaseContext(String);
+
+impl CaseContext {
+    fn enter(name: &str, tc: &Value) -> Self {
+        Self(format!("{name}/{tc}"))
+    }
+}
+
+impl Drop for CaseContext {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!("panicked while evaluating case {}", self.0);
+        }
+    }
+}
+
+/// Load `ignore.txt` from the fixtures directory: one `helper/input` key per
+/// line, blank lines and `#`-comments skipped. A matching key downgrades a
+/// failing case to "ignored" instead of failing the run — for known-broken
+/// cases that shouldn't block CI while they're tracked.
+fn load_ignore_list() -> BTreeSet<String> {
+    let path = format!("{FIXTURES_DIR}/ignore.txt");
+    std::fs::read_to_string(&path)
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Run every `[helper, input, expected]` case in `helpers.json` as a
+/// conformance suite: execute all of them (no panic on the first failure or
+/// unknown helper), print a pass/failed/ignored summary, write a
+/// machine-readable results artifact for diffing across runs, and fail the
+/// test once at the end if any non-ignored case failed.
 #[test]
 fn test_helper_fixtures() {
-    let cases = load_fixture("helpers.json");
-    for case in cases.as_array().expect("helpers.json must be an array") {
+    let fixtures = load_fixture("helpers.json");
+    let ignore = load_ignore_list();
+    let registry = helper_registry();
+
+    let mut results = Vec::new();
+    let mut failures = Vec::new();
+    let mut passed = 0;
+    let mut ignored = 0;
+
+    for case in fixtures.as_array().expect("helpers.json must be an array") {
         let name = case["name"].as_str().expect("missing name");
         let test_cases = case["cases"].as_array().expect("missing cases");
-        match name {
-            "format_bytes_human" => {
-                for tc in test_cases {
-                    let arr = tc.as_array().expect("case must be [input, expected]");
-                    let input = arr[0].as_i64().expect("input must be i64");
-                    let expected = arr[1].as_str().expect("expected must be string");
-                    assert_eq!(format_bytes_human(input), expected, "[helpers/format_bytes_human({input})]");
-                }
-            }
-            "format_with_commas" => {
-                for tc in test_cases {
-                    let arr = tc.as_array().expect("case must be [input, expected]");
-                    let input = arr[0].as_u64().expect("input must be u64");
-                    let expected = arr[1].as_str().expect("expected must be string");
-                    assert_eq!(format_with_commas(input), expected, "[helpers/format_with_commas({input})]");
+        for tc in test_cases {
+            let _ctx = CaseContext::enter(name, tc);
+            let (key, outcome) = eval_helper_case(&registry, name, tc);
+            let status = match &outcome {
+                Ok(()) => {
+                    passed += 1;
+                    "passed"
                 }
-            }
-            "extract_currency_code" => {
-                for tc in test_cases {
-                    let arr = tc.as_array().expect("case must be [input, expected]");
-                    let input = arr[0].as_str().expect("input must be string");
-                    let expected = if arr[1].is_null() { None } else { arr[1].as_str() };
-                    assert_eq!(extract_currency_code(input), expected, "[helpers/extract_currency_code({input})]");
+                Err(_) if ignore.contains(&key) => {
+                    ignored += 1;
+                    "ignored"
                 }
-            }
-            "parse_size" => {
-                for tc in test_cases {
-                    let arr = tc.as_array().expect("case must be [input, expected]");
-                    let input = arr[0].as_str().expect("input must be string");
-                    let expected = if arr[1].is_null() { None } else { arr[1].as_u64() };
-                    assert_eq!(parse_size(input), expected, "[helpers/parse_size({input:?})]");
+                Err(message) => {
+                    failures.push((key.clone(), message.clone()));
+                    "failed"
                 }
-            }
-            other => panic!("unknown helper: {other}"),
+            };
+            results.push(json!({
+                "key": key,
+                "status": status,
+                "message": outcome.err(),
+            }));
+        }
+    }
+
+    let total = results.len();
+    let failed = failures.len();
+    println!("helper conformance: {passed}/{total} passed, {failed} failed, {ignored} ignored");
+
+    let report = json!({
+        "total": total,
+        "passed": passed,
+        "failed": failed,
+        "ignored": ignored,
+        "cases": results,
+    });
+    let out_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/target/conformance");
+    if std::fs::create_dir_all(out_dir).is_ok() {
+        let _ = std::fs::write(format!("{out_dir}/helpers.json"), serde_json::to_string_pretty(&report).unwrap());
+    }
+
+    if !failures.is_empty() {
+        for (key, message) in &failures {
+            println!("FAILED {key}: {message}");
         }
+        panic!("{failed} of {total} helper case(s) failed (see output above)");
     }
 }
 
@@ -329,6 +510,21 @@ fn yaml_strip_secret() {
     assert!(!out.contains("api_key_secret"));
 }
 
+#[test]
+fn yaml_object_valued_secret_redacted() {
+    let out = output_yaml(&json!({"creds_secret": {"password": "hunter2"}}));
+    assert!(out.contains("creds: \"***\""));
+    assert!(!out.contains("hunter2"));
+}
+
+#[test]
+fn yaml_array_valued_secret_redacted() {
+    let out = output_yaml(&json!({"tokens_secret": ["a", "b"]}));
+    assert!(out.contains("tokens: \"***\""));
+    assert!(!out.contains("- a"));
+    assert!(!out.contains("- b"));
+}
+
 #[test]
 fn yaml_strip_percent() {
     let out = output_yaml(&json!({"cpu_percent": 85}));
@@ -530,13 +726,13 @@ fn yaml_fmt_epoch_s() {
 #[test]
 fn yaml_fmt_bytes() {
     let out = output_yaml(&json!({"file_size_bytes": 5242880}));
-    assert!(out.contains("\"5.0MB\""));
+    assert!(out.contains("\"5.0MiB\""));
 }
 
 #[test]
 fn yaml_fmt_bytes_kb() {
     let out = output_yaml(&json!({"payload_bytes": 456789}));
-    assert!(out.contains("\"446.1KB\""));
+    assert!(out.contains("\"446.1KiB\""));
 }
 
 #[test]
@@ -714,6 +910,18 @@ fn plain_secrets_redacted() {
     assert!(!out.contains("real-key"));
 }
 
+#[test]
+fn plain_object_valued_secret_redacted() {
+    let out = output_plain(&json!({"creds_secret": {"password": "hunter2"}}));
+    assert_eq!(out, "creds=***");
+}
+
+#[test]
+fn plain_array_valued_secret_redacted() {
+    let out = output_plain(&json!({"tokens_secret": ["a", "b"]}));
+    assert_eq!(out, "tokens=***");
+}
+
 #[test]
 fn plain_empty_object() {
     let out = output_plain(&json!({}));
@@ -919,7 +1127,7 @@ fn negative_bytes_small() {
 #[test]
 fn negative_bytes_mb() {
     let out = output_plain(&json!({"delta_bytes": -5242880}));
-    assert_eq!(out, "delta=-5.0MB");
+    assert_eq!(out, "delta=-5.0MiB");
 }
 
 // ═══════════════════════════════════════════
@@ -1041,6 +1249,61 @@ fn redact_non_string_redacted() {
     assert_eq!(v["count_secret"], "***");
 }
 
+#[test]
+fn redact_partial_reveals_edges() {
+    let mut v = json!({"api_key_secret": "sk-1234567890cdef"});
+    internal_redact_secrets_mode(&mut v, RedactMode::Partial);
+    assert_eq!(v["api_key_secret"], "sk-1***cdef");
+}
+
+#[test]
+fn redact_partial_falls_back_to_full_when_too_short() {
+    let mut v = json!({"api_key_secret": "sk-123"});
+    internal_redact_secrets_mode(&mut v, RedactMode::Partial);
+    assert_eq!(v["api_key_secret"], "***");
+}
+
+#[test]
+fn redact_partial_boundary_exactly_eight_chars_falls_back() {
+    let mut v = json!({"api_key_secret": "12345678"});
+    internal_redact_secrets_mode(&mut v, RedactMode::Partial);
+    assert_eq!(v["api_key_secret"], "***");
+}
+
+#[test]
+fn redact_fingerprint_is_stable_and_short() {
+    let mut a = json!({"api_key_secret": "sk-123"});
+    let mut b = json!({"api_key_secret": "sk-123"});
+    internal_redact_secrets_mode(&mut a, RedactMode::Fingerprint);
+    internal_redact_secrets_mode(&mut b, RedactMode::Fingerprint);
+    assert_eq!(a, b);
+    assert_eq!(a["api_key_secret"].as_str().unwrap().len(), 8);
+}
+
+#[test]
+fn redact_fingerprint_differs_for_different_values() {
+    let mut a = json!({"api_key_secret": "sk-123"});
+    let mut b = json!({"api_key_secret": "sk-456"});
+    internal_redact_secrets_mode(&mut a, RedactMode::Fingerprint);
+    internal_redact_secrets_mode(&mut b, RedactMode::Fingerprint);
+    assert_ne!(a, b);
+}
+
+#[test]
+fn redact_mode_applies_recursively() {
+    let mut v = json!({"config": {"password_secret": "sk-1234567890cdef"}});
+    internal_redact_secrets_mode(&mut v, RedactMode::Partial);
+    assert_eq!(v["config"]["password_secret"], "sk-1***cdef");
+}
+
+#[test]
+fn redact_rules_with_mode() {
+    let rules = RuleSet::load("rules:\n  - suffix: _session_token\n    redact: true\n").unwrap();
+    let mut v = json!({"auth_session_token": "tok-1234567890abcd"});
+    internal_redact_secrets_all(&mut v, &rules, RedactMode::Partial);
+    assert_eq!(v["auth_session_token"], "tok-***abcd");
+}
+
 // ═══════════════════════════════════════════
 // Complete integration: README examples
 // ═══════════════════════════════════════════
@@ -1065,7 +1328,7 @@ fn readme_complete_suffix_yaml() {
     assert!(out.contains("cache_ttl: \"3600s\""));
     assert!(out.contains("count: 42"));
     assert!(out.contains("created_at: \"2025-02-07T00:00:00.000Z\""));
-    assert!(out.contains("file_size: \"5.0MB\""));
+    assert!(out.contains("file_size: \"5.0MiB\""));
     assert!(out.contains("payment: \"50000000msats\""));
     assert!(out.contains("price: \"$99.99\""));
     assert!(out.contains("request_timeout: \"5.0s\""));
@@ -1090,7 +1353,7 @@ fn readme_complete_suffix_plain() {
     let out = output_plain(&data);
     assert_eq!(
         out,
-        "api_key=*** cache_ttl=3600s count=42 created_at=2025-02-07T00:00:00.000Z file_size=5.0MB payment=50000000msats price=$99.99 request_timeout=5.0s success_rate=95.5% user_name=alice"
+        "api_key=*** cache_ttl=3600s count=42 created_at=2025-02-07T00:00:00.000Z file_size=5.0MiB payment=50000000msats price=$99.99 request_timeout=5.0s success_rate=95.5% user_name=alice"
     );
 }
 
@@ -1159,3 +1422,1326 @@ fn readme_jsonl_output() {
     assert!(!out.contains("sk-123"));
     assert!(!out.contains('\n'));
 }
+
+// ═══════════════════════════════════════════
+// TimeMode::Relative — humanized "time ago" rendering
+// ═══════════════════════════════════════════
+
+#[test]
+fn relative_epoch_ms_minutes_ago() {
+    let now = 1_738_886_400_000i64;
+    let out = output_plain_mode(&json!({"created_epoch_ms": now - 180_000}), TimeMode::Relative(now));
+    assert_eq!(out, "created=3m ago");
+}
+
+#[test]
+fn relative_epoch_ms_in_future() {
+    let now = 1_738_886_400_000i64;
+    let out = output_plain_mode(&json!({"expires_epoch_ms": now + 45_000}), TimeMode::Relative(now));
+    assert_eq!(out, "expires=in 45s");
+}
+
+#[test]
+fn relative_two_significant_units() {
+    let now = 1_738_886_400_000i64;
+    let out = output_plain_mode(&json!({"created_epoch_ms": now - 3_900_000}), TimeMode::Relative(now));
+    assert_eq!(out, "created=1h 5m ago");
+}
+
+#[test]
+fn relative_just_now() {
+    let now = 1_738_886_400_000i64;
+    let out = output_plain_mode(&json!({"created_epoch_ms": now - 1_000}), TimeMode::Relative(now));
+    assert_eq!(out, "created=just now");
+}
+
+#[test]
+fn relative_epoch_s_and_ns() {
+    let now_ms = 1_738_886_400_000i64;
+    let out = output_plain_mode(
+        &json!({"cached_epoch_s": now_ms / 1000 - 60, "pinged_epoch_ns": (now_ms - 7_200_000) * 1_000_000}),
+        TimeMode::Relative(now_ms),
+    );
+    assert!(out.contains("cached=1m ago"));
+    assert!(out.contains("pinged=2h ago"));
+}
+
+#[test]
+fn relative_rfc3339_parsed_and_diffed() {
+    let now = 1_738_886_400_000i64; // 2025-02-07T00:00:00.000Z
+    let out = output_plain_mode(&json!({"expires_rfc3339": "2025-02-06T23:00:00Z"}), TimeMode::Relative(now));
+    assert_eq!(out, "expires=1h ago");
+}
+
+#[test]
+fn relative_yaml_quotes_the_humanized_value() {
+    let now = 1_738_886_400_000i64;
+    let out = output_yaml_mode(&json!({"created_epoch_ms": now - 180_000}), TimeMode::Relative(now));
+    assert!(out.contains("created: \"3m ago\""));
+}
+
+#[test]
+fn absolute_mode_unaffected_by_relative_addition() {
+    let out = output_plain(&json!({"created_at_epoch_ms": 1738886400000i64}));
+    assert_eq!(out, "created_at=2025-02-07T00:00:00.000Z");
+}
+
+#[test]
+fn relative_days_threshold() {
+    let now = 1_738_886_400_000i64;
+    let out = output_plain_mode(&json!({"expires_epoch_ms": now - 3 * 86_400_000}), TimeMode::Relative(now));
+    assert_eq!(out, "expires=3d ago");
+}
+
+#[test]
+fn relative_weeks_threshold() {
+    let now = 1_738_886_400_000i64;
+    let out = output_plain_mode(&json!({"expires_epoch_ms": now - 14 * 86_400_000}), TimeMode::Relative(now));
+    assert_eq!(out, "expires=2w ago");
+}
+
+#[test]
+fn relative_months_threshold() {
+    let now = 1_738_886_400_000i64;
+    let out = output_plain_mode(&json!({"created_epoch_s": now / 1000 - 3 * 2_629_746}), TimeMode::Relative(now));
+    assert_eq!(out, "created=3mo ago");
+}
+
+#[test]
+fn relative_years_threshold() {
+    let now = 1_738_886_400_000i64;
+    let out =
+        output_plain_mode(&json!({"created_epoch_ns": (now - 2 * 31_557_600_000) * 1_000_000}), TimeMode::Relative(now));
+    assert_eq!(out, "created=2y ago");
+}
+
+// ═══════════════════════════════════════════
+// Inverse parsing — parse_plain / parse_yaml
+// ═══════════════════════════════════════════
+
+#[test]
+fn parse_plain_flat_fields() {
+    let out = output_plain(&json!({"name": "widget", "count": 3, "active": true}));
+    assert_eq!(parse_plain(&out), json!({"name": "widget", "count": 3, "active": true}));
+}
+
+#[test]
+fn parse_plain_nested_dotted_keys() {
+    let out = output_plain(&json!({"trace": {"request_id": "abc"}, "user": {"id": 7}}));
+    assert_eq!(parse_plain(&out), json!({"trace": {"request_id": "abc"}, "user": {"id": 7}}));
+}
+
+#[test]
+fn parse_plain_quoted_value_with_spaces() {
+    assert_eq!(parse_plain(r#"message="hello world" count=2"#), json!({"message": "hello world", "count": 2}));
+}
+
+#[test]
+fn parse_plain_array_field() {
+    let out = output_plain(&json!({"fields": ["email", "age"]}));
+    assert_eq!(parse_plain(&out), json!({"fields": ["email", "age"]}));
+}
+
+#[test]
+fn parse_plain_null_field() {
+    assert_eq!(parse_plain("deleted_at="), json!({"deleted_at": Value::Null}));
+}
+
+#[test]
+fn parse_plain_duration_ms_round_trips() {
+    let out = output_plain(&json!({"request_timeout_ms": 1280}));
+    assert_eq!(out, "request_timeout=1.28s");
+    assert_eq!(parse_plain(&out), json!({"request_timeout_ms": 1280}));
+}
+
+#[test]
+fn parse_plain_duration_ms_under_second_round_trips() {
+    let out = output_plain(&json!({"latency_ms": 42}));
+    assert_eq!(parse_plain(&out), json!({"latency_ms": 42}));
+}
+
+#[test]
+fn parse_plain_duration_s_round_trips() {
+    let out = output_plain(&json!({"cache_ttl_s": 3600}));
+    assert_eq!(out, "cache_ttl=3600s");
+    assert_eq!(parse_plain(&out), json!({"cache_ttl_s": 3600}));
+}
+
+#[test]
+fn parse_plain_duration_ns_and_us_round_trip() {
+    let out = output_plain(&json!({"query_us": 830, "page_fault_ns": 450000}));
+    assert_eq!(parse_plain(&out), json!({"query_us": 830, "page_fault_ns": 450000}));
+}
+
+#[test]
+fn parse_plain_bytes_round_trips() {
+    let out = output_plain(&json!({"upload_bytes": 5242880}));
+    assert_eq!(out, "upload=5.0MiB");
+    assert_eq!(parse_plain(&out), json!({"upload_bytes": 5242880}));
+}
+
+#[test]
+fn parse_plain_bytes_small_round_trips() {
+    let out = output_plain(&json!({"chunk_bytes": 512}));
+    assert_eq!(parse_plain(&out), json!({"chunk_bytes": 512}));
+}
+
+#[test]
+fn parse_plain_usd_cents_round_trips() {
+    let out = output_plain(&json!({"price_usd_cents": 9999}));
+    assert_eq!(out, "price=$99.99");
+    assert_eq!(parse_plain(&out), json!({"price_usd_cents": 9999}));
+}
+
+#[test]
+fn parse_plain_secret_stays_redacted() {
+    let out = output_plain(&json!({"api_key_secret": "sk-live-123"}));
+    assert_eq!(out, "api_key=***");
+    assert_eq!(parse_plain(&out), json!({"api_key_secret": "***"}));
+}
+
+#[test]
+fn parse_plain_unrecognized_string_passes_through() {
+    assert_eq!(parse_plain("status=pending"), json!({"status": "pending"}));
+}
+
+#[test]
+fn parse_yaml_flat_fields() {
+    let out = output_yaml(&json!({"name": "widget", "count": 3, "active": false}));
+    assert_eq!(parse_yaml(&out), json!({"name": "widget", "count": 3, "active": false}));
+}
+
+#[test]
+fn parse_yaml_nested_object() {
+    let out = output_yaml(&json!({"trace": {"request_id": "abc", "span": {"depth": 2}}}));
+    assert_eq!(parse_yaml(&out), json!({"trace": {"request_id": "abc", "span": {"depth": 2}}}));
+}
+
+#[test]
+fn parse_yaml_array_of_scalars() {
+    let out = output_yaml(&json!({"tags": ["a", "b", "c"]}));
+    assert_eq!(parse_yaml(&out), json!({"tags": ["a", "b", "c"]}));
+}
+
+#[test]
+fn parse_yaml_array_of_objects() {
+    let out = output_yaml(&json!({"items": [{"id": 1}, {"id": 2}]}));
+    assert_eq!(parse_yaml(&out), json!({"items": [{"id": 1}, {"id": 2}]}));
+}
+
+#[test]
+fn parse_yaml_empty_object_and_array() {
+    let out = output_yaml(&json!({"meta": {}, "tags": []}));
+    assert_eq!(parse_yaml(&out), json!({"meta": {}, "tags": []}));
+}
+
+#[test]
+fn parse_yaml_duration_round_trips() {
+    let out = output_yaml(&json!({"request_timeout_ms": 1280}));
+    assert_eq!(parse_yaml(&out), json!({"request_timeout_ms": 1280}));
+}
+
+#[test]
+fn parse_yaml_bytes_round_trips() {
+    let out = output_yaml(&json!({"upload_bytes": 5242880}));
+    assert_eq!(parse_yaml(&out), json!({"upload_bytes": 5242880}));
+}
+
+#[test]
+fn parse_yaml_usd_cents_round_trips() {
+    let out = output_yaml(&json!({"price_usd_cents": 9999}));
+    assert_eq!(parse_yaml(&out), json!({"price_usd_cents": 9999}));
+}
+
+#[test]
+fn parse_yaml_secret_stays_redacted() {
+    let out = output_yaml(&json!({"api_key_secret": "sk-live-123"}));
+    assert_eq!(parse_yaml(&out), json!({"api_key_secret": "***"}));
+}
+
+// ═══════════════════════════════════════════
+// User-defined rules (rules.rs)
+// ═══════════════════════════════════════════
+
+use rules::RuleSet;
+
+#[test]
+fn rules_empty_config_leaves_builtin_behavior_unchanged() {
+    let rules = RuleSet::default();
+    assert_eq!(
+        output_plain_rules(&json!({"latency_ms": 42}), TimeMode::Absolute, &rules),
+        output_plain(&json!({"latency_ms": 42})),
+    );
+}
+
+#[test]
+fn rules_passthrough_unit() {
+    let rules = RuleSet::load("rules:\n  - suffix: _kwh\n    format: { kind: passthrough, unit: kWh }\n").unwrap();
+    let out = output_plain_rules(&json!({"usage_kwh": 12}), TimeMode::Absolute, &rules);
+    assert_eq!(out, "usage=12 kWh");
+}
+
+#[test]
+fn rules_currency_with_symbol_and_decimals() {
+    let rules = RuleSet::load(
+        "rules:\n  - suffix: _house_credits\n    format: { kind: currency, symbol: \"H$\", decimals: 2 }\n",
+    )
+    .unwrap();
+    let out = output_plain_rules(&json!({"balance_house_credits": 1050}), TimeMode::Absolute, &rules);
+    assert_eq!(out, "balance=H$10.50");
+}
+
+#[test]
+fn rules_percent() {
+    let rules = RuleSet::load("rules:\n  - suffix: _load\n    format: { kind: percent }\n").unwrap();
+    let out = output_plain_rules(&json!({"cpu_load": 87}), TimeMode::Absolute, &rules);
+    assert_eq!(out, "cpu=87%");
+}
+
+#[test]
+fn rules_duration_matches_builtin_ms_rendering() {
+    let rules = RuleSet::load("rules:\n  - suffix: _elapsed\n    format: { kind: duration }\n").unwrap();
+    let out = output_plain_rules(&json!({"step_elapsed": 1280}), TimeMode::Absolute, &rules);
+    assert_eq!(out, "step=1.28s");
+}
+
+#[test]
+fn rules_bytes_matches_builtin_bytes_rendering() {
+    let rules = RuleSet::load("rules:\n  - suffix: _size\n    format: { kind: bytes }\n").unwrap();
+    let out = output_plain_rules(&json!({"upload_size": 5242880}), TimeMode::Absolute, &rules);
+    assert_eq!(out, "upload=5.0MiB");
+}
+
+#[test]
+fn rules_redact_true_hides_value_and_skips_format() {
+    let rules = RuleSet::load("rules:\n  - suffix: _session_token\n    redact: true\n").unwrap();
+    let out = output_plain_rules(&json!({"auth_session_token": "tok-abc"}), TimeMode::Absolute, &rules);
+    assert_eq!(out, "auth=***");
+}
+
+#[test]
+fn rules_redact_also_applies_to_internal_redact_secrets_rules() {
+    let rules = RuleSet::load("rules:\n  - suffix: _session_token\n    redact: true\n").unwrap();
+    let mut value = json!({"auth_session_token": "tok-abc"});
+    internal_redact_secrets_rules(&mut value, &rules);
+    assert_eq!(value, json!({"auth_session_token": "***"}));
+}
+
+#[test]
+fn rules_user_suffix_overrides_builtin_suffix() {
+    let rules = RuleSet::load("rules:\n  - suffix: _ms\n    format: { kind: passthrough, unit: millis }\n").unwrap();
+    let out = output_plain_rules(&json!({"latency_ms": 42}), TimeMode::Absolute, &rules);
+    assert_eq!(out, "latency=42 millis");
+}
+
+#[test]
+fn rules_no_match_falls_back_to_builtin_table() {
+    let rules = RuleSet::load("rules:\n  - suffix: _kwh\n    format: { kind: passthrough, unit: kWh }\n").unwrap();
+    let out = output_plain_rules(&json!({"price_usd_cents": 9999}), TimeMode::Absolute, &rules);
+    assert_eq!(out, "price=$99.99");
+}
+
+#[test]
+fn rules_merged_over_keeps_user_rule_on_shared_suffix() {
+    let user = RuleSet::load("rules:\n  - suffix: _ms\n    format: { kind: passthrough, unit: millis }\n").unwrap();
+    let defaults = RuleSet::load("rules:\n  - suffix: _ms\n    format: { kind: duration }\n").unwrap();
+    let merged = user.merged_over(defaults);
+    let out = output_plain_rules(&json!({"latency_ms": 42}), TimeMode::Absolute, &merged);
+    assert_eq!(out, "latency=42 millis");
+}
+
+#[test]
+fn rules_merged_over_adds_non_conflicting_default_rules() {
+    let user = RuleSet::load("rules:\n  - suffix: _kwh\n    format: { kind: passthrough, unit: kWh }\n").unwrap();
+    let defaults = RuleSet::load("rules:\n  - suffix: _house_credits\n    format: { kind: currency, symbol: \"H$\", decimals: 2 }\n").unwrap();
+    let merged = user.merged_over(defaults);
+    let out = output_plain_rules(&json!({"balance_house_credits": 1050}), TimeMode::Absolute, &merged);
+    assert_eq!(out, "balance=H$10.50");
+}
+
+#[test]
+fn rules_load_invalid_yaml_errs() {
+    assert!(RuleSet::load("not: [valid: yaml").is_err());
+}
+
+// ═══════════════════════════════════════════
+// Suffix registry — SuffixRegistry
+// ═══════════════════════════════════════════
+
+#[test]
+fn registry_custom_suffix_formats_value() {
+    let registry = SuffixRegistry::empty().register("_wei", |v| {
+        format!("{:.2} ETH", v.as_f64().unwrap_or(0.0) / 1e18)
+    });
+    let out = output_plain_registry(&json!({"balance_wei": 1_500_000_000_000_000_000i64}), &registry);
+    assert_eq!(out, "balance=1.50 ETH");
+}
+
+#[test]
+fn registry_new_prepopulates_value_only_builtins() {
+    let registry = SuffixRegistry::new();
+    let out = output_plain_registry(&json!({"latency_ms": 42}), &registry);
+    assert_eq!(out, "latency=42ms");
+}
+
+#[test]
+fn registry_register_overrides_builtin_suffix() {
+    let registry = SuffixRegistry::new().register("_ms", |v| format!("{} millis", crate::plain_scalar(v)));
+    let out = output_plain_registry(&json!({"latency_ms": 42}), &registry);
+    assert_eq!(out, "latency=42 millis");
+}
+
+#[test]
+fn registry_register_replaces_existing_entry_for_same_suffix() {
+    let registry = SuffixRegistry::empty()
+        .register("_wei", |v| format!("{} first", crate::plain_scalar(v)))
+        .register("_wei", |v| format!("{} second", crate::plain_scalar(v)));
+    let out = output_plain_registry(&json!({"balance_wei": 1}), &registry);
+    assert_eq!(out, "balance=1 second");
+}
+
+#[test]
+fn registry_prefers_longest_matching_suffix() {
+    let registry = SuffixRegistry::empty()
+        .register("_ms", |_| "short".to_string())
+        .register("_epoch_ms", |_| "long".to_string());
+    let out = output_plain_registry(&json!({"created_epoch_ms": 1}), &registry);
+    assert_eq!(out, "created=long");
+}
+
+#[test]
+fn registry_no_match_falls_back_to_builtin_table() {
+    let registry = SuffixRegistry::empty().register("_wei", |v| {
+        format!("{:.2} ETH", v.as_f64().unwrap_or(0.0) / 1e18)
+    });
+    let out = output_plain_registry(&json!({"price_usd_cents": 999}), &registry);
+    assert_eq!(out, "price=$9.99");
+}
+
+#[test]
+fn registry_checked_after_rules() {
+    let rules = RuleSet::load("rules:\n  - suffix: _wei\n    format: { kind: passthrough, unit: wei }\n").unwrap();
+    let registry = SuffixRegistry::empty().register("_wei", |v| {
+        format!("{:.2} ETH", v.as_f64().unwrap_or(0.0) / 1e18)
+    });
+    let out = output_plain_all(&json!({"balance_wei": 5}), TimeMode::Absolute, ByteUnits::default(), &rules, &registry);
+    assert_eq!(out, "balance=5 wei");
+}
+
+// ═══════════════════════════════════════════
+// Query-string output — output_query
+// ═══════════════════════════════════════════
+
+#[test]
+fn query_flat_fields() {
+    let out = output_query(&json!({"name": "widget", "count": 3}));
+    assert_eq!(out, "count=3&name=widget");
+}
+
+#[test]
+fn query_nested_dotted_keys() {
+    let out = output_query(&json!({"trace": {"request_id": "abc"}}));
+    assert_eq!(out, "trace.request_id=abc");
+}
+
+#[test]
+fn query_array_field_comma_joined() {
+    let out = output_query(&json!({"fields": ["email", "age"]}));
+    assert_eq!(out, "fields=email%2Cage");
+}
+
+#[test]
+fn query_space_percent_encoded_not_quoted() {
+    let out = output_query(&json!({"message": "hello world"}));
+    assert_eq!(out, "message=hello%20world");
+}
+
+#[test]
+fn query_reserved_chars_percent_encoded() {
+    let out = output_query(&json!({"callback": "https://example.com/a?b=c&d=e"}));
+    assert_eq!(out, "callback=https%3A%2F%2Fexample.com%2Fa%3Fb%3Dc%26d%3De");
+}
+
+#[test]
+fn query_duration_ms_formatted() {
+    let out = output_query(&json!({"request_timeout_ms": 1280}));
+    assert_eq!(out, "request_timeout=1.28s");
+}
+
+#[test]
+fn query_bytes_formatted() {
+    let out = output_query(&json!({"upload_bytes": 5242880}));
+    assert_eq!(out, "upload=5.0MiB");
+}
+
+#[test]
+fn query_usd_cents_formatted() {
+    let out = output_query(&json!({"price_usd_cents": 9999}));
+    assert_eq!(out, "price=%2499.99");
+}
+
+#[test]
+fn query_secret_redacted() {
+    let out = output_query(&json!({"api_key_secret": "sk-live-123"}));
+    assert_eq!(out, "api_key=%2A%2A%2A");
+}
+
+#[test]
+fn query_object_valued_secret_redacted() {
+    let out = output_query(&json!({"creds_secret": {"password": "hunter2"}}));
+    assert_eq!(out, "creds=%2A%2A%2A");
+}
+
+#[test]
+fn query_keys_sorted() {
+    let out = output_query(&json!({"zeta": 1, "alpha": 2}));
+    assert_eq!(out, "alpha=2&zeta=1");
+}
+
+#[test]
+fn query_unreserved_chars_left_unescaped() {
+    let out = output_query(&json!({"slug": "a-b_c.d~e"}));
+    assert_eq!(out, "slug=a-b_c.d~e");
+}
+
+#[test]
+fn cli_parse_output_accepts_query() {
+    assert_eq!(cli_parse_output("query").unwrap(), OutputFormat::Query);
+}
+
+#[test]
+fn cli_output_query_matches_output_query() {
+    let value = json!({"name": "widget"});
+    assert_eq!(cli_output(&value, OutputFormat::Query), output_query(&value));
+}
+
+// ═══════════════════════════════════════════
+// Streaming NDJSON — FrameWriter (stream.rs)
+// ═══════════════════════════════════════════
+
+use stream::FrameWriter;
+
+#[test]
+fn test_stream_fixtures() {
+    let cases = load_fixture("stream.json");
+    for case in cases.as_array().expect("stream.json must be an array") {
+        let name = case["name"].as_str().expect("missing name");
+        let frames = case["frames"].as_array().expect("missing frames");
+
+        let mut buf = Vec::new();
+        let mut writer = FrameWriter::new(&mut buf);
+        for frame in frames {
+            writer.write_frame(frame).unwrap_or_else(|e| panic!("[stream/{name}] write_frame failed: {e}"));
+        }
+        assert!(writer.is_terminated(), "[stream/{name}] writer should be terminated");
+
+        let out = String::from_utf8(buf).unwrap_or_else(|e| panic!("[stream/{name}] invalid utf8: {e}"));
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), frames.len(), "[stream/{name}] line count");
+        for (line, frame) in lines.iter().zip(frames) {
+            assert!(!line.contains('\n'), "[stream/{name}] line is not single-line");
+            let mut expected = frame.clone();
+            internal_redact_secrets(&mut expected);
+            let actual: Value = serde_json::from_str(line).unwrap_or_else(|e| panic!("[stream/{name}] invalid json line: {e}"));
+            assert_eq!(actual, expected, "[stream/{name}]");
+        }
+    }
+}
+
+#[test]
+fn stream_rejects_frame_after_terminal_ok() {
+    let mut buf = Vec::new();
+    let mut writer = FrameWriter::new(&mut buf);
+    writer.write_frame(&build_json_ok(json!({}), None)).unwrap();
+    let err = writer.write_frame(&build_json("progress", json!({"current": 1}), None));
+    assert!(err.is_err());
+}
+
+#[test]
+fn stream_rejects_frame_after_terminal_error() {
+    let mut buf = Vec::new();
+    let mut writer = FrameWriter::new(&mut buf);
+    writer.write_frame(&build_json_error("boom", None)).unwrap();
+    let err = writer.write_frame(&build_json_ok(json!({}), None));
+    assert!(err.is_err());
+}
+
+#[test]
+fn stream_not_terminated_before_terminal_frame() {
+    let mut buf = Vec::new();
+    let mut writer = FrameWriter::new(&mut buf);
+    writer.write_frame(&build_json("progress", json!({"current": 1}), None)).unwrap();
+    assert!(!writer.is_terminated());
+}
+
+#[test]
+fn stream_writes_redacted_single_line_json() {
+    let mut buf = Vec::new();
+    let mut writer = FrameWriter::new(&mut buf);
+    writer
+        .write_frame(&build_json("progress", json!({"api_key_secret": "sk-live-123"}), None))
+        .unwrap();
+    let out = String::from_utf8(buf).unwrap();
+    assert_eq!(out.lines().count(), 1);
+    assert!(out.contains("\"***\""));
+    assert!(!out.contains("sk-live-123"));
+}
+
+// ═══════════════════════════════════════════
+// ByteUnits — decimal (SI) vs binary (IEC) byte formatting
+// ═══════════════════════════════════════════
+
+#[test]
+fn bytes_binary_is_the_default() {
+    let out = output_plain(&json!({"upload_bytes": 5242880}));
+    assert_eq!(out, "upload=5.0MiB");
+}
+
+#[test]
+fn bytes_units_decimal_plain() {
+    let out = output_plain_units(&json!({"upload_bytes": 5_000_000}), ByteUnits::Decimal);
+    assert_eq!(out, "upload=5.0MB");
+}
+
+#[test]
+fn bytes_units_decimal_divides_by_1000() {
+    let out = output_plain_units(&json!({"upload_bytes": 5242880}), ByteUnits::Decimal);
+    assert_eq!(out, "upload=5.2MB");
+}
+
+#[test]
+fn bytes_units_binary_explicit_matches_default() {
+    let out = output_plain_units(&json!({"upload_bytes": 5242880}), ByteUnits::Binary);
+    assert_eq!(out, "upload=5.0MiB");
+}
+
+#[test]
+fn bytes_units_decimal_yaml() {
+    let out = output_yaml_units(&json!({"upload_bytes": 1_500_000}), ByteUnits::Decimal);
+    assert!(out.contains("upload: \"1.5MB\""));
+}
+
+#[test]
+fn bytes_units_negative_preserves_sign() {
+    let out = output_plain_units(&json!({"delta_bytes": -5_000_000}), ByteUnits::Decimal);
+    assert_eq!(out, "delta=-5.0MB");
+}
+
+#[test]
+fn bytes_units_full_combines_mode_and_units() {
+    let now = 1_738_886_400_000i64;
+    let out = output_plain_full(
+        &json!({"created_epoch_ms": now - 180_000, "upload_bytes": 5_000_000}),
+        TimeMode::Relative(now),
+        ByteUnits::Decimal,
+        &RuleSet::default(),
+    );
+    assert_eq!(out, "created=3m ago upload=5.0MB");
+}
+
+#[test]
+fn bytes_units_terabyte_decimal() {
+    let out = output_plain_units(&json!({"size_bytes": 2_000_000_000_000i64}), ByteUnits::Decimal);
+    assert_eq!(out, "size=2.0TB");
+}
+
+#[test]
+fn parse_plain_binary_bytes_round_trips() {
+    let out = output_plain(&json!({"upload_bytes": 5242880}));
+    assert_eq!(out, "upload=5.0MiB");
+    assert_eq!(parse_plain(&out), json!({"upload_bytes": 5242880}));
+}
+
+// ═══════════════════════════════════════════
+// Inverse parsing — RFC 3339 and byte-base disambiguation
+// ═══════════════════════════════════════════
+
+#[test]
+fn parse_plain_rfc3339_recovers_epoch_ms() {
+    let parsed = parse_plain("created=2025-02-07T00:00:00.000Z");
+    assert_eq!(parsed, json!({"created_epoch_ms": 1738886400000i64}));
+}
+
+#[test]
+fn parse_yaml_rfc3339_recovers_epoch_ms() {
+    let parsed = parse_yaml("created: \"2025-02-07T00:00:00.000Z\"");
+    assert_eq!(parsed, json!({"created_epoch_ms": 1738886400000i64}));
+}
+
+#[test]
+fn parse_plain_units_decimal_bytes_round_trip() {
+    let out = output_plain_units(&json!({"upload_bytes": 5_000_000}), ByteUnits::Decimal);
+    assert_eq!(out, "upload=5.0MB");
+    assert_eq!(
+        parse_plain_units(&out, ByteUnits::Decimal),
+        json!({"upload_bytes": 5_000_000})
+    );
+}
+
+#[test]
+fn parse_plain_default_bytes_assumes_binary() {
+    // Without an explicit units argument, a bare "MB" is resolved as binary
+    // (matching `ByteUnits::default`) — lossy for documents actually
+    // produced with `ByteUnits::Decimal`.
+    let parsed = parse_plain("upload=5.0MB");
+    assert_eq!(parsed, json!({"upload_bytes": 5242880}));
+}
+
+#[test]
+fn parse_plain_unambiguous_ib_suffix_ignores_units_argument() {
+    let parsed = parse_plain_units("upload=5.0MiB", ByteUnits::Decimal);
+    assert_eq!(parsed, json!({"upload_bytes": 5242880}));
+}
+
+#[test]
+fn parse_yaml_units_decimal_bytes_round_trip() {
+    let out = output_yaml_units(&json!({"upload_bytes": 1_500_000}), ByteUnits::Decimal);
+    assert_eq!(
+        parse_yaml_units(&out, ByteUnits::Decimal),
+        json!({"upload_bytes": 1_500_000})
+    );
+}
+
+#[test]
+fn parse_plain_redacted_value_stays_redacted() {
+    let parsed = parse_plain("api_key=***");
+    assert_eq!(parsed, json!({"api_key_secret": "***"}));
+}
+
+#[test]
+fn parse_plain_bare_number_stays_a_plain_number() {
+    let parsed = parse_plain("count=42");
+    assert_eq!(parsed, json!({"count": 42}));
+}
+
+// ═══════════════════════════════════════════
+// AFD tracing layer (afd_tracing.rs)
+// ═══════════════════════════════════════════
+
+use afd_tracing::{render_event, LogFormat};
+use tracing::Level;
+
+#[test]
+fn tracing_event_defaults_code_from_level() {
+    let line = render_event(
+        LogFormat::Json,
+        Level::WARN,
+        "my_crate::db",
+        0,
+        None,
+        None,
+        Vec::new(),
+        None,
+        Vec::new(),
+    );
+    let value: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["code"], "warn");
+}
+
+#[test]
+fn tracing_event_explicit_code_overrides_level_default() {
+    let line = render_event(
+        LogFormat::Json,
+        Level::ERROR,
+        "my_crate::db",
+        0,
+        None,
+        None,
+        Vec::new(),
+        None,
+        vec![("code".to_string(), json!("retry"))],
+    );
+    let value: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["code"], "retry");
+}
+
+#[test]
+fn tracing_event_includes_message_and_target() {
+    let line = render_event(
+        LogFormat::Json,
+        Level::INFO,
+        "my_crate::db",
+        1_700_000_000_000,
+        None,
+        None,
+        Vec::new(),
+        Some("connected".to_string()),
+        Vec::new(),
+    );
+    let value: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["message"], "connected");
+    assert_eq!(value["target"], "my_crate::db");
+    assert_eq!(value["timestamp_epoch_ms"], 1_700_000_000_000i64);
+}
+
+#[test]
+fn tracing_event_folds_in_span_fields() {
+    let line = render_event(
+        LogFormat::Json,
+        Level::INFO,
+        "my_crate::db",
+        0,
+        None,
+        None,
+        vec![("request_id".to_string(), json!("abc-123"))],
+        None,
+        Vec::new(),
+    );
+    let value: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["request_id"], "abc-123");
+}
+
+#[test]
+fn tracing_event_fields_override_span_fields_on_collision() {
+    let line = render_event(
+        LogFormat::Json,
+        Level::INFO,
+        "my_crate::db",
+        0,
+        None,
+        None,
+        vec![("status".to_string(), json!("pending"))],
+        None,
+        vec![("status".to_string(), json!("done"))],
+    );
+    let value: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["status"], "done");
+}
+
+#[test]
+fn tracing_event_redacts_secret_fields() {
+    let line = render_event(
+        LogFormat::Json,
+        Level::INFO,
+        "my_crate::auth",
+        0,
+        None,
+        None,
+        Vec::new(),
+        None,
+        vec![("token_secret".to_string(), json!("sk-live-123"))],
+    );
+    assert!(line.contains("\"***\""));
+    assert!(!line.contains("sk-live-123"));
+}
+
+#[test]
+fn tracing_event_plain_strips_suffixes_and_humanizes() {
+    let line = render_event(
+        LogFormat::Plain,
+        Level::INFO,
+        "my_crate::db",
+        0,
+        None,
+        None,
+        vec![("trace.duration_ms".to_string(), json!(1280))],
+        None,
+        Vec::new(),
+    );
+    assert!(line.contains("trace.duration=1.28s"));
+}
+
+#[test]
+fn tracing_event_yaml_quotes_humanized_values() {
+    let line = render_event(
+        LogFormat::Yaml,
+        Level::INFO,
+        "my_crate::db",
+        0,
+        None,
+        None,
+        Vec::new(),
+        None,
+        vec![("upload_bytes".to_string(), json!(5242880))],
+    );
+    assert!(line.contains("upload: \"5.0MiB\""));
+}
+
+#[test]
+fn tracing_event_omits_span_and_spans_by_default() {
+    let line = render_event(
+        LogFormat::Json,
+        Level::INFO,
+        "my_crate::db",
+        0,
+        Some("handle_request".to_string()),
+        Some(vec!["server".to_string(), "handle_request".to_string()]),
+        Vec::new(),
+        None,
+        Vec::new(),
+    );
+    let value: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["span"], "handle_request");
+    assert_eq!(value["spans"], json!(["server", "handle_request"]));
+}
+
+#[test]
+fn tracing_event_span_and_spans_absent_when_not_provided() {
+    let line = render_event(
+        LogFormat::Json,
+        Level::INFO,
+        "my_crate::db",
+        0,
+        None,
+        None,
+        Vec::new(),
+        None,
+        Vec::new(),
+    );
+    let value: Value = serde_json::from_str(&line).unwrap();
+    assert!(value.get("span").is_none());
+    assert!(value.get("spans").is_none());
+}
+
+#[test]
+fn afd_layer_with_writer_routes_output_to_it_instead_of_stdout() {
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for SharedBuf {
+        type Writer = SharedBuf;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let layer = afd_tracing::AfdLayer::builder(LogFormat::Json)
+        .with_writer(SharedBuf(buf.clone()))
+        .build();
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(code = "ok", "hello from the buffer");
+    });
+
+    let output = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+    let value: Value = serde_json::from_str(output.trim_end()).unwrap();
+    assert_eq!(value["message"], "hello from the buffer");
+}
+
+#[test]
+fn tracing_event_compact_drops_target_and_timestamp() {
+    let line = render_event(
+        LogFormat::Compact,
+        Level::INFO,
+        "my_crate::db",
+        1_700_000_000_000,
+        None,
+        None,
+        vec![("request_id".to_string(), json!("abc-123"))],
+        Some("connected".to_string()),
+        Vec::new(),
+    );
+    let value: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(value["message"], "connected");
+    assert_eq!(value["request_id"], "abc-123");
+    assert_eq!(value["code"], "info");
+    assert!(value.get("target").is_none());
+    assert!(value.get("timestamp_epoch_ms").is_none());
+}
+
+#[test]
+fn tracing_event_silent_renders_empty_line() {
+    let line = render_event(
+        LogFormat::Silent,
+        Level::INFO,
+        "my_crate::db",
+        0,
+        None,
+        None,
+        Vec::new(),
+        Some("connected".to_string()),
+        Vec::new(),
+    );
+    assert_eq!(line, "");
+}
+
+#[test]
+fn afd_layer_silent_suppresses_output_but_spans_still_run() {
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for SharedBuf {
+        type Writer = SharedBuf;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let layer = afd_tracing::AfdLayer::builder(LogFormat::Silent)
+        .with_writer(SharedBuf(buf.clone()))
+        .build();
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("work");
+        let _enter = span.enter();
+        tracing::info!("should not appear");
+    });
+
+    assert!(buf.lock().unwrap().is_empty());
+}
+
+#[test]
+fn afd_layer_records_error_source_chain() {
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Debug)]
+    struct RootCause;
+
+    impl fmt::Display for RootCause {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "connection refused")
+        }
+    }
+
+    impl std::error::Error for RootCause {}
+
+    #[derive(Debug)]
+    struct WrappedError(RootCause);
+
+    impl fmt::Display for WrappedError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "failed to connect to database")
+        }
+    }
+
+    impl std::error::Error for WrappedError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for SharedBuf {
+        type Writer = SharedBuf;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let layer = afd_tracing::AfdLayer::builder(LogFormat::Json)
+        .with_writer(SharedBuf(buf.clone()))
+        .build();
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    let err = WrappedError(RootCause);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::error!(error = &err as &dyn std::error::Error, "query failed");
+    });
+
+    let output = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+    let value: Value = serde_json::from_str(output.trim_end()).unwrap();
+    assert_eq!(value["error"]["message"], "failed to connect to database");
+    assert_eq!(value["error"]["causes"], json!(["connection refused"]));
+}
+
+#[test]
+fn afd_layer_span_timing_emits_span_close_with_durations() {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for SharedBuf {
+        type Writer = SharedBuf;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let layer = afd_tracing::AfdLayer::builder(LogFormat::Json)
+        .with_span_timing(true)
+        .with_writer(SharedBuf(buf.clone()))
+        .build();
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("db_query", query = "SELECT 1");
+        let _enter = span.enter();
+        thread::sleep(Duration::from_millis(5));
+    });
+
+    let output = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+    let value: Value = serde_json::from_str(output.trim_end()).unwrap();
+    assert_eq!(value["code"], "span_close");
+    assert_eq!(value["span"], "db_query");
+    assert_eq!(value["query"], "SELECT 1");
+    assert!(value["duration_ms"].as_u64().unwrap() >= 5);
+    assert!(value["busy_ms"].as_u64().unwrap() >= 5);
+    assert_eq!(value["busy_ms"], value["duration_ms"]);
+}
+
+#[test]
+fn afd_layer_without_span_timing_emits_no_span_close_line() {
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for SharedBuf {
+        type Writer = SharedBuf;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let layer = afd_tracing::AfdLayer::builder(LogFormat::Json)
+        .with_writer(SharedBuf(buf.clone()))
+        .build();
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("db_query");
+        let _enter = span.enter();
+    });
+
+    assert!(buf.lock().unwrap().is_empty());
+}
+
+#[test]
+fn log_format_from_str_parses_all_variants_case_insensitively() {
+    assert!(matches!("json".parse::<LogFormat>(), Ok(LogFormat::Json)));
+    assert!(matches!("PLAIN".parse::<LogFormat>(), Ok(LogFormat::Plain)));
+    assert!(matches!("Yaml".parse::<LogFormat>(), Ok(LogFormat::Yaml)));
+    assert!(matches!("compact".parse::<LogFormat>(), Ok(LogFormat::Compact)));
+    assert!(matches!("silent".parse::<LogFormat>(), Ok(LogFormat::Silent)));
+    assert!(matches!("none".parse::<LogFormat>(), Ok(LogFormat::Silent)));
+    assert!("xml".parse::<LogFormat>().is_err());
+}
+
+#[test]
+fn parse_json_fields_off_by_default_leaves_json_looking_strings_as_plain_text() {
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for SharedBuf {
+        type Writer = SharedBuf;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let layer = afd_tracing::AfdLayer::builder(LogFormat::Json)
+        .with_writer(SharedBuf(buf.clone()))
+        .build();
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(payload = "{\"a\":1}", "event");
+    });
+
+    let output = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+    let value: Value = serde_json::from_str(output.trim_end()).unwrap();
+    assert_eq!(value["payload"], "{\"a\":1}");
+}
+
+#[test]
+fn parse_json_fields_on_nests_json_looking_strings() {
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for SharedBuf {
+        type Writer = SharedBuf;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let layer = afd_tracing::AfdLayer::builder(LogFormat::Json)
+        .with_parse_json_fields(true)
+        .with_writer(SharedBuf(buf.clone()))
+        .build();
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(payload = "{\"a\":1}", array = "[1,2,3]", "event");
+    });
+
+    let output = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+    let value: Value = serde_json::from_str(output.trim_end()).unwrap();
+    assert_eq!(value["payload"], serde_json::json!({"a": 1}));
+    assert_eq!(value["array"], serde_json::json!([1, 2, 3]));
+}
+
+#[test]
+fn json_prefixed_field_is_always_parsed_regardless_of_flag() {
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for SharedBuf {
+        type Writer = SharedBuf;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let layer = afd_tracing::AfdLayer::builder(LogFormat::Json)
+        .with_writer(SharedBuf(buf.clone()))
+        .build();
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    let payload = serde_json::json!({"nested": true});
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(
+            "json.payload" = %afd_tracing::json_value(&payload),
+            "event"
+        );
+    });
+
+    let output = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+    let value: Value = serde_json::from_str(output.trim_end()).unwrap();
+    assert_eq!(value["payload"], serde_json::json!({"nested": true}));
+    assert!(value.get("json.payload").is_none());
+}
+
+#[test]
+fn json_prefixed_field_falls_back_to_string_on_parse_failure() {
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for SharedBuf {
+        type Writer = SharedBuf;
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let layer = afd_tracing::AfdLayer::builder(LogFormat::Json)
+        .with_writer(SharedBuf(buf.clone()))
+        .build();
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("json.payload" = "not json", "event");
+    });
+
+    let output = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+    let value: Value = serde_json::from_str(output.trim_end()).unwrap();
+    assert_eq!(value["payload"], "not json");
+}